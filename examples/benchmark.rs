@@ -0,0 +1,47 @@
+use std::time::Instant;
+
+use rpi_led_panel::{RGBMatrix, RGBMatrixConfig};
+
+/// Frames to measure for each configuration.
+const FRAMES: usize = 300;
+
+/// A refresh rate that no configuration reaches, so that the update thread never waits between frames.
+const UNREACHABLE_REFRESH_RATE: usize = 100_000;
+
+fn main() {
+    let config: RGBMatrixConfig = argh::from_env();
+    if config.dry_run {
+        println!("{}", config.summary());
+        return;
+    }
+
+    println!("Measuring the achievable refresh rate over {FRAMES} frames.");
+    println!("chain length | PWM bits | refresh rate | thread load");
+    for chain_length in 1..=config.chain_length {
+        for pwm_bits in (1..=config.pwm_bits).rev() {
+            let config = RGBMatrixConfig {
+                chain_length,
+                pwm_bits,
+                refresh_rate: UNREACHABLE_REFRESH_RATE,
+                min_pwm_bits: None,
+                quiet: true,
+                ..config.clone()
+            };
+            let (mut matrix, mut canvas) =
+                RGBMatrix::new(config, 0).expect("Matrix initialization failed");
+
+            let start = Instant::now();
+            for step in 0..FRAMES {
+                let level = (step % 256) as u8;
+                canvas.fill(level, 255 - level, level / 2);
+                canvas = matrix.update_on_vsync(canvas);
+            }
+            let refresh_rate = FRAMES as f64 / start.elapsed().as_secs_f64();
+
+            println!(
+                "{chain_length:>12} | {pwm_bits:>8} | {refresh_rate:>9.0} Hz | {:>10.0}%",
+                100.0 * matrix.thread_cpu_load()
+            );
+        }
+    }
+}
@@ -12,6 +12,10 @@ use rpi_led_panel::{RGBMatrix, RGBMatrixConfig};
 
 fn main() {
     let config: RGBMatrixConfig = argh::from_env();
+    if config.dry_run {
+        println!("{}", config.summary());
+        return;
+    }
     let rows = config.rows as i32;
     let cols = config.cols as i32;
 
@@ -46,7 +50,7 @@ fn main() {
     );
 
     for step in 0.. {
-        canvas.fill(0, 0, 0);
+        canvas.clear();
         circle.draw(canvas.as_mut()).unwrap();
         [diagonal1, diagonal2, top, bottom, left, right]
             .iter()
@@ -13,6 +13,10 @@ const IMAGE_SIZE: usize = 64;
 
 fn main() {
     let config: RGBMatrixConfig = argh::from_env();
+    if config.dry_run {
+        println!("{}", config.summary());
+        return;
+    }
     let rows = config.rows;
     let cols = config.cols;
     let (mut matrix, mut canvas) = RGBMatrix::new(config, 0).expect("Matrix initialization failed");
@@ -27,7 +31,7 @@ fn main() {
     );
 
     for step in 0.. {
-        canvas.fill(0, 0, 0);
+        canvas.clear();
         image.draw(canvas.as_mut()).unwrap();
         canvas = matrix.update_on_vsync(canvas);
 
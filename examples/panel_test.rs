@@ -0,0 +1,134 @@
+use std::{
+    io::stdin,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread::spawn,
+};
+
+use rpi_led_panel::{
+    Canvas, LedSequence, MultiplexMapperType, RGBMatrix, RGBMatrixConfig, RowAddressSetterType,
+};
+
+const ROW_SETTERS: [RowAddressSetterType; 5] = [
+    RowAddressSetterType::Direct,
+    RowAddressSetterType::ShiftRegister,
+    RowAddressSetterType::DirectABCDLine,
+    RowAddressSetterType::ABCShiftRegister,
+    RowAddressSetterType::SM5266,
+];
+
+const LED_SEQUENCES: [LedSequence; 6] = [
+    LedSequence::Rgb,
+    LedSequence::Rbg,
+    LedSequence::Grb,
+    LedSequence::Gbr,
+    LedSequence::Brg,
+    LedSequence::Bgr,
+];
+
+/// Draw a white frame around the canvas, a yellow diagonal from the top left and red, green and blue corners
+/// at the top left, top right and bottom left.
+fn draw_pattern(canvas: &mut Canvas) {
+    let (width, height) = canvas.dimensions();
+    canvas.clear();
+    for x in 0..width {
+        canvas.set_pixel(x, 0, 255, 255, 255);
+        canvas.set_pixel(x, height - 1, 255, 255, 255);
+    }
+    for y in 0..height {
+        canvas.set_pixel(0, y, 255, 255, 255);
+        canvas.set_pixel(width - 1, y, 255, 255, 255);
+    }
+    for i in 0..width.min(height) {
+        canvas.set_pixel(i, i, 255, 255, 0);
+    }
+    let corner = (width.min(height) / 4).max(1);
+    canvas.fill_region([1, 1, corner, corner], 255, 0, 0);
+    canvas.fill_region([width - 1 - corner, 1, corner, corner], 0, 255, 0);
+    canvas.fill_region([1, height - 1 - corner, corner, corner], 0, 0, 255);
+}
+
+/// Forward the first character of each line typed on the terminal.
+fn spawn_keyboard() -> Receiver<char> {
+    let (sender, receiver) = channel();
+    spawn(move || {
+        for line in stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let key = line.trim().chars().next().unwrap_or('m');
+            if sender.send(key.to_ascii_lowercase()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn main() {
+    let config: RGBMatrixConfig = argh::from_env();
+    if config.dry_run {
+        println!("{}", config.summary());
+        return;
+    }
+
+    println!("Look for a white frame around the panel, a yellow diagonal from the top left and");
+    println!("red, green and blue corners at the top left, top right and bottom left.");
+    println!("Type a letter and press enter to try the next multiplexing (m, or just enter),");
+    println!("row setter (r) or LED sequence (s), or to quit (q).");
+
+    let keyboard = spawn_keyboard();
+    // The first multiplexing is none at all.
+    let mut multiplexing: usize = 0;
+    let mut row_setter = 0;
+    let mut led_sequence = 0;
+    loop {
+        let config = RGBMatrixConfig {
+            multiplexing: multiplexing
+                .checked_sub(1)
+                .map(|index| MultiplexMapperType::all()[index]),
+            row_setter: ROW_SETTERS[row_setter],
+            led_sequence: LED_SEQUENCES[led_sequence],
+            quiet: true,
+            ..config.clone()
+        };
+        let options = format!(
+            "{}--row-setter {:?} --led-sequence {:?}",
+            config
+                .multiplexing
+                .map(|multiplexing| format!("--multiplexing {multiplexing:?} "))
+                .unwrap_or_default(),
+            config.row_setter,
+            config.led_sequence
+        );
+
+        let key = match RGBMatrix::new(config, 0) {
+            Ok((mut matrix, mut canvas)) => {
+                println!("Showing {options}");
+                loop {
+                    draw_pattern(&mut canvas);
+                    canvas = matrix.update_on_vsync(canvas);
+                    match keyboard.try_recv() {
+                        Ok('s') => {
+                            led_sequence = (led_sequence + 1) % LED_SEQUENCES.len();
+                            matrix.set_led_sequence(LED_SEQUENCES[led_sequence]);
+                            println!("Showing the LED sequence {:?}", LED_SEQUENCES[led_sequence]);
+                        }
+                        Ok(key) => break key,
+                        Err(TryRecvError::Empty) => {}
+                        Err(TryRecvError::Disconnected) => break 'q',
+                    }
+                }
+            }
+            Err(error) => {
+                println!("Skipping {options}: {error}");
+                keyboard.recv().unwrap_or('q')
+            }
+        };
+        match key {
+            'q' => break,
+            'r' => row_setter = (row_setter + 1) % ROW_SETTERS.len(),
+            's' => led_sequence = (led_sequence + 1) % LED_SEQUENCES.len(),
+            _ => multiplexing = (multiplexing + 1) % (MultiplexMapperType::all().len() + 1),
+        }
+    }
+}
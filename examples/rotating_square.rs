@@ -21,6 +21,10 @@ fn rotate([x, y]: [isize; 2], angle: f64) -> [f64; 2] {
 
 fn main() {
     let config: RGBMatrixConfig = argh::from_env();
+    if config.dry_run {
+        println!("{}", config.summary());
+        return;
+    }
     let rows = config.rows as isize;
     let cols = config.cols as isize;
     let (mut matrix, mut canvas) = RGBMatrix::new(config, 0).expect("Matrix initialization failed");
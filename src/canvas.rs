@@ -1,8 +1,16 @@
-use std::{error::Error, str::FromStr};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 use crate::{
-    color::ColorLookup, config::K_BIT_PLANES, gpio::Gpio, hardware_mapping::HardwareMapping,
-    row_address_setter::RowAddressSetter, RGBMatrixConfig,
+    color::{brightness_level, ColorLookup, Palette},
+    config::{K_BIT_PLANES, SUB_PANELS_PER_CHAIN},
+    gpio::Gpio,
+    hardware_mapping::HardwareMapping,
+    row_address_setter::RowAddressSetter,
+    RGBMatrixConfig,
 };
 
 #[derive(Clone, Copy)]
@@ -62,6 +70,70 @@ impl LedSequence {
     }
 }
 
+/// The order in which the rows of each sub-panel are refreshed, e.g. to reduce the rolling bar that shows up
+/// when filming the panels with a rolling-shutter camera.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RowOrder {
+    /// From top to bottom.
+    #[default]
+    Sequential,
+    /// The even rows from top to bottom, then the odd rows.
+    Interlaced,
+    /// The given rows, which have to contain every row of a sub-panel exactly once. Parsed from a
+    /// comma-separated list, e.g. `"0,4,1,5,2,6,3,7"`.
+    Custom(Vec<usize>),
+}
+
+impl FromStr for RowOrder {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sequential" => Ok(Self::Sequential),
+            "interlaced" => Ok(Self::Interlaced),
+            _ => s
+                .split(',')
+                .map(|row| {
+                    row.trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid row order: {s}").into())
+                })
+                .collect::<Result<_, _>>()
+                .map(Self::Custom),
+        }
+    }
+}
+
+impl RowOrder {
+    /// The rows of a sub-panel with `double_rows` rows in the order they are refreshed, or `None` if a custom
+    /// order doesn't contain every row exactly once.
+    pub(crate) fn rows(&self, double_rows: usize) -> Option<Vec<usize>> {
+        match self {
+            RowOrder::Sequential => Some((0..double_rows).collect()),
+            RowOrder::Interlaced => {
+                // Round up so that an odd number of double rows still shows every row.
+                let half_double = double_rows.div_ceil(2);
+                Some(
+                    (0..double_rows)
+                        .map(|row_loop| {
+                            if row_loop < half_double {
+                                2 * row_loop
+                            } else {
+                                2 * (row_loop - half_double) + 1
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            RowOrder::Custom(rows) => {
+                let mut sorted = rows.clone();
+                sorted.sort_unstable();
+                sorted.into_iter().eq(0..double_rows).then(|| rows.clone())
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct PixelDesignator {
     gpio_word: Option<usize>,
@@ -85,6 +157,28 @@ impl PixelDesignator {
             mask: !0u32,
         }
     }
+
+    /// Mark the pixel as not mapped to the matrix.
+    pub(crate) fn set_unused(&mut self) {
+        self.gpio_word = None;
+    }
+
+    /// Reassign the color bits, which were assigned with the sequence `from`, to the channels of `to`.
+    fn change_sequence(&mut self, from: LedSequence, to: LedSequence) {
+        // Find the red, green and blue pins by asking `from` which of them it assigned to each channel.
+        let mut pins = [0; 3];
+        for (channel, bits) in [
+            (Channel::First, self.r_bit),
+            (Channel::Second, self.g_bit),
+            (Channel::Third, self.b_bit),
+        ] {
+            pins[from.get_gpio(channel, 0, 1, 2) as usize] = bits;
+        }
+        let [r, g, b] = pins;
+        self.r_bit = to.get_gpio(Channel::First, r, g, b);
+        self.g_bit = to.get_gpio(Channel::Second, r, g, b);
+        self.b_bit = to.get_gpio(Channel::Third, r, g, b);
+    }
 }
 
 #[derive(Clone)]
@@ -93,6 +187,18 @@ pub(crate) struct PixelDesignatorMap {
     height: usize,
     pixel_designator: PixelDesignator,
     buffer: Vec<PixelDesignator>,
+    /// Whether every matrix pixel is visible exactly once. Only then can [`Canvas::fill`] write whole rows
+    /// at once instead of going through the designators.
+    one_to_one: bool,
+    /// The LED sequences of the upper and the lower sub-panels.
+    led_sequences: [LedSequence; 2],
+    /// The color bits of the upper sub-panels of all chains, to tell which sequence a designator has.
+    upper_color_bits: u32,
+    /// The number of matrix pixels along each axis that a visible pixel is shown on.
+    scale: usize,
+    /// The designators of the other matrix pixels of each block after [`PixelDesignatorMap::upscaled`],
+    /// `scale * scale - 1` per visible pixel in row-major order.
+    copies: Vec<PixelDesignator>,
 }
 
 impl PixelDesignatorMap {
@@ -112,17 +218,26 @@ impl PixelDesignatorMap {
                 let offset = (y % double_rows) * (width * K_BIT_PLANES) + x;
                 d.gpio_word = Some(offset);
 
+                // Each chain drives two sub-panels. Panels with more sub-panels use the following chains.
                 let panel = y / config.rows;
-                let color_bits = hm.panels.color_bits[panel];
-                let (r, g, b) = if y - panel * config.rows < double_rows {
+                let sub_panel = (y % config.rows) / double_rows;
+                let color_set =
+                    panel * config.chains_per_panel() * SUB_PANELS_PER_CHAIN + sub_panel;
+                let color_bits = hm.chain_color_bits(color_set / SUB_PANELS_PER_CHAIN);
+                let first_sub_panel = color_set.is_multiple_of(SUB_PANELS_PER_CHAIN);
+                let (r, g, b) = if first_sub_panel {
                     (color_bits.r1, color_bits.g1, color_bits.b1)
                 } else {
                     (color_bits.r2, color_bits.g2, color_bits.b2)
                 };
+                let sequence = match config.led_sequence_lower {
+                    Some(sequence) if !first_sub_panel => sequence,
+                    _ => config.led_sequence,
+                };
 
-                d.r_bit = config.led_sequence.get_gpio(Channel::First, r, g, b);
-                d.g_bit = config.led_sequence.get_gpio(Channel::Second, r, g, b);
-                d.b_bit = config.led_sequence.get_gpio(Channel::Third, r, g, b);
+                d.r_bit = sequence.get_gpio(Channel::First, r, g, b);
+                d.g_bit = sequence.get_gpio(Channel::Second, r, g, b);
+                d.b_bit = sequence.get_gpio(Channel::Third, r, g, b);
                 d.mask = !(d.r_bit | d.g_bit | d.b_bit);
             }
         }
@@ -131,9 +246,76 @@ impl PixelDesignatorMap {
             height,
             pixel_designator,
             buffer,
+            one_to_one: true,
+            led_sequences: [
+                config.led_sequence,
+                config.led_sequence_lower.unwrap_or(config.led_sequence),
+            ],
+            upper_color_bits: hm.panels.color_bits.iter().fold(0, |bits, color_bits| {
+                bits | color_bits.r1 | color_bits.g1 | color_bits.b1
+            }),
+            scale: 1,
+            copies: Vec::new(),
         }
     }
 
+    /// An empty map of another size with the same properties, to be filled by a pixel mapper. Unlike
+    /// [`PixelDesignatorMap::new`], this doesn't assume that the new size fits on the matrix.
+    pub(crate) fn resized(&self, width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixel_designator: self.pixel_designator,
+            buffer: vec![self.pixel_designator; width * height],
+            one_to_one: true,
+            led_sequences: self.led_sequences,
+            upper_color_bits: self.upper_color_bits,
+            scale: 1,
+            copies: Vec::new(),
+        }
+    }
+
+    /// A map with each visible pixel shown on a block of `scale` x `scale` pixels of this map. Pixels that
+    /// don't fill a whole block at the right and bottom edges are left dark.
+    pub(crate) fn upscaled(&self, scale: usize) -> Self {
+        let scale = scale.max(1);
+        if scale == 1 {
+            return self.clone();
+        }
+        let [width, height] = [self.width / scale, self.height / scale];
+        let mut upscaled = self.resized(width, height);
+        upscaled.scale = scale;
+        upscaled.copies = Vec::with_capacity(width * height * (scale * scale - 1));
+        for y in 0..height {
+            for x in 0..width {
+                *upscaled.get_mut(x, y).unwrap() = *self.get(x * scale, y * scale).unwrap();
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        if dx != 0 || dy != 0 {
+                            let copy = self.get(x * scale + dx, y * scale + dy).unwrap();
+                            upscaled.copies.push(*copy);
+                        }
+                    }
+                }
+            }
+        }
+        upscaled.one_to_one = self.one_to_one
+            && self.width.is_multiple_of(scale)
+            && self.height.is_multiple_of(scale);
+        upscaled
+    }
+
+    /// The designators of the other matrix pixels that a visible pixel is shown on.
+    pub(crate) fn copies(&self, x: usize, y: usize) -> &[PixelDesignator] {
+        let count = self.scale * self.scale - 1;
+        let start = (y * self.width + x) * count;
+        &self.copies[start..start + count]
+    }
+
+    pub(crate) fn scale(&self) -> usize {
+        self.scale
+    }
+
     pub(crate) fn get(&self, x: usize, y: usize) -> Option<&PixelDesignator> {
         let position = (y * self.width) + x;
         self.buffer.get(position)
@@ -148,6 +330,41 @@ impl PixelDesignatorMap {
         self.pixel_designator
     }
 
+    pub(crate) fn set_one_to_one(&mut self, one_to_one: bool) {
+        self.one_to_one = one_to_one;
+    }
+
+    pub(crate) fn is_one_to_one(&self) -> bool {
+        self.one_to_one
+    }
+
+    /// Whether all sub-panels use the same LED sequence, so that [`PixelDesignatorMap::pixel_designator`] has
+    /// the right color bits for every pixel.
+    pub(crate) fn has_uniform_sequence(&self) -> bool {
+        self.led_sequences[0] == self.led_sequences[1]
+    }
+
+    /// The LED sequence of the upper sub-panels.
+    pub(crate) fn led_sequence(&self) -> LedSequence {
+        self.led_sequences[0]
+    }
+
+    /// Reassign the color bits of all pixels for a new LED sequence of all sub-panels.
+    pub(crate) fn set_led_sequence(&mut self, sequence: LedSequence) {
+        let [upper, lower] = self.led_sequences;
+        for designator in self.buffer.iter_mut().chain(&mut self.copies) {
+            let bits = designator.r_bit | designator.g_bit | designator.b_bit;
+            let from = if bits & self.upper_color_bits != 0 {
+                upper
+            } else {
+                lower
+            };
+            designator.change_sequence(from, sequence);
+        }
+        self.pixel_designator.change_sequence(upper, sequence);
+        self.led_sequences = [sequence; 2];
+    }
+
     pub(crate) fn width(&self) -> usize {
         self.width
     }
@@ -157,36 +374,149 @@ impl PixelDesignatorMap {
     }
 }
 
+/// A pixel outside of the canvas, returned by [`Canvas::try_set_pixel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
+    /// The width of the canvas.
+    pub width: usize,
+    /// The height of the canvas.
+    pub height: usize,
+}
+
+impl Error for OutOfBounds {}
+
+impl Display for OutOfBounds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The pixel ({}, {}) is outside of the {}x{} canvas.",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+/// Display parameters that [`crate::RGBMatrix`] sets on every canvas it swaps, so that they don't depend on which
+/// canvas is being drawn. `None` keeps the value of the canvas.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DisplaySettings {
+    /// The brightness level from 0 (off) to 255.
+    pub(crate) brightness: Option<u8>,
+    pub(crate) pwm_bits: Option<usize>,
+    pub(crate) chain_gains: Option<Vec<u8>>,
+    pub(crate) led_sequence: Option<LedSequence>,
+}
+
+/// The content of an overlay canvas that [`crate::RGBMatrix::set_overlay`] draws over every frame. Black overlay
+/// pixels are transparent.
+pub(crate) struct Overlay {
+    bitplane_buffer: Vec<u32>,
+    /// The color bits of the non-transparent overlay pixels in each word of the bit planes.
+    mask: Vec<u32>,
+}
+
+impl Overlay {
+    pub(crate) fn new(canvas: &Canvas) -> Self {
+        let mut mask = vec![0; canvas.bitplane_buffer.len()];
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let designator = *canvas
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                if canvas.get_luminance(designator) == [0, 0, 0] {
+                    continue;
+                }
+                let copies = canvas.shared_mapper.copies(x, y);
+                for designator in std::iter::once(&designator).chain(copies) {
+                    let Some(pos_start) = designator.gpio_word else {
+                        continue;
+                    };
+                    let bits = designator.r_bit | designator.g_bit | designator.b_bit;
+                    for plane in 0..K_BIT_PLANES {
+                        mask[pos_start + canvas.matrix_cols * plane] |= bits;
+                    }
+                }
+            }
+        }
+        Self {
+            bitplane_buffer: canvas.bitplane_buffer.clone(),
+            mask,
+        }
+    }
+
+    /// Whether the overlay was drawn on a canvas with the bit planes of `canvas`.
+    pub(crate) fn fits(&self, canvas: &Canvas) -> bool {
+        self.bitplane_buffer.len() == canvas.bitplane_buffer.len()
+    }
+}
+
 #[derive(Clone)]
 pub struct Canvas {
-    #[allow(unused)]
-    rows: usize,
-    cols: usize,
+    /// Rows of a single panel, as configured.
+    panel_rows: usize,
+    /// Columns of a single panel, as configured.
+    panel_cols: usize,
+    chain_length: usize,
+    parallel: usize,
+    /// Columns of the whole matrix, i.e. of all chained panels.
+    matrix_cols: usize,
     double_rows: usize,
     bitplane_buffer: Vec<u32>,
     shared_mapper: PixelDesignatorMap,
     pwm_bits: usize,
+    /// The brightness level from 0 (off) to 255.
     brightness: u8,
     color_lookup: ColorLookup,
-    interlaced: bool,
+    /// The double rows in the order they are refreshed.
+    row_order: Vec<usize>,
+    /// Whether the panels clock in data on the falling edge of an idle high clock.
+    clock_falling_edge: bool,
+    /// Whether writes to unused pixels are reported.
+    report_unused_writes: bool,
+    /// The brightness in percent of each panel along the chain. Empty if all panels have full brightness.
+    chain_gains: Vec<u8>,
+    /// The `[x, y, width, height]` of the region that is left untouched by [`Canvas::fill`] and
+    /// [`Canvas::clear`].
+    protected_region: Option<[usize; 4]>,
 }
 
 impl Canvas {
-    pub(crate) fn new(config: &RGBMatrixConfig, shared_mapper: PixelDesignatorMap) -> Self {
-        let color_lookup = ColorLookup::new_cie1931();
-        let rows = config.rows * config.parallel;
-        let cols = config.cols * config.chain_length;
+    /// Create a new canvas. The `panel_size` is the `[rows, cols]` of a single panel as configured, before
+    /// any multiplexing mapper changed them.
+    pub(crate) fn new(
+        config: &RGBMatrixConfig,
+        shared_mapper: PixelDesignatorMap,
+        [panel_rows, panel_cols]: [usize; 2],
+    ) -> Self {
+        let color_lookup = ColorLookup::new_cie1931(config.black_level_lift);
+        let matrix_cols = config.cols * config.chain_length;
         let double_rows = config.double_rows();
         Self {
-            rows,
-            cols,
+            panel_rows,
+            panel_cols,
+            chain_length: config.chain_length,
+            parallel: config.parallel,
+            matrix_cols,
             double_rows,
-            bitplane_buffer: vec![0u32; double_rows * cols * K_BIT_PLANES],
+            bitplane_buffer: vec![0u32; double_rows * matrix_cols * K_BIT_PLANES],
             shared_mapper,
             pwm_bits: config.pwm_bits,
-            brightness: config.led_brightness.clamp(1, 100),
+            brightness: brightness_level(config.led_brightness.max(1)),
             color_lookup,
-            interlaced: config.interlaced,
+            row_order: config
+                .row_order()
+                .rows(double_rows)
+                .unwrap_or_else(|| (0..double_rows).collect()),
+            clock_falling_edge: config.clock_falling_edge,
+            report_unused_writes: false,
+            chain_gains: config
+                .chain_gains
+                .as_ref()
+                .map(|gains| gains.0.clone())
+                .unwrap_or_default(),
+            protected_region: None,
         }
     }
 
@@ -200,47 +530,292 @@ impl Canvas {
         self.shared_mapper.width
     }
 
+    /// The `(width, height)` of the canvas.
+    #[must_use]
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+
+    /// The number of pixels of the canvas.
+    #[must_use]
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    /// Check if a pixel is within the canvas. Pixels outside of it are discarded by [`Canvas::set_pixel`].
+    #[must_use]
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width() && y < self.height()
+    }
+
+    /// The number of rows of a single panel, as configured. Unlike [`Canvas::height`], this is not affected
+    /// by pixel mappers.
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.panel_rows
+    }
+
+    /// The number of columns of a single panel, as configured. Unlike [`Canvas::width`], this is not affected
+    /// by pixel mappers.
+    #[must_use]
+    pub fn cols(&self) -> usize {
+        self.panel_cols
+    }
+
+    /// The number of daisy-chained panels.
+    #[must_use]
+    pub fn chain_length(&self) -> usize {
+        self.chain_length
+    }
+
+    /// The number of chains running in parallel.
+    #[must_use]
+    pub fn parallel(&self) -> usize {
+        self.parallel
+    }
+
     fn position_at(&self, double_row: usize, column: usize, bit: usize) -> usize {
-        double_row * (self.cols * K_BIT_PLANES) + bit * self.cols + column
+        double_row * (self.matrix_cols * K_BIT_PLANES) + bit * self.matrix_cols + column
     }
 
     fn row_at(&self, double_row: usize, column: usize, bit: usize) -> &[u32] {
         let start = self.position_at(double_row, column, bit);
-        &self.bitplane_buffer[start..start + self.cols]
+        &self.bitplane_buffer[start..start + self.matrix_cols]
     }
 
     fn row_at_mut(&mut self, double_row: usize, column: usize, bit: usize) -> &mut [u32] {
         let start = self.position_at(double_row, column, bit);
-        &mut self.bitplane_buffer[start..start + self.cols]
+        &mut self.bitplane_buffer[start..start + self.matrix_cols]
     }
 
     pub fn set_pixel(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
-        if x >= self.width() || y >= self.height() {
+        if !self.contains(x, y) {
             return;
         }
-        let designator = self
+        self.set_pixel_unchecked(x, y, r, g, b);
+    }
+
+    /// Like [`Canvas::set_pixel`], but returns an error instead of ignoring pixels outside of the canvas, e.g. to
+    /// find mistakes in the coordinate calculations during development.
+    pub fn try_set_pixel(
+        &mut self,
+        x: usize,
+        y: usize,
+        r: u8,
+        g: u8,
+        b: u8,
+    ) -> Result<(), OutOfBounds> {
+        if !self.contains(x, y) {
+            return Err(OutOfBounds {
+                x,
+                y,
+                width: self.width(),
+                height: self.height(),
+            });
+        }
+        self.set_pixel_unchecked(x, y, r, g, b);
+        Ok(())
+    }
+
+    /// Get the designator of a pixel that is about to be written. Writes to unused pixels are reported if
+    /// enabled with [`Canvas::set_report_unused_writes`].
+    fn designator_for_write(&self, x: usize, y: usize) -> PixelDesignator {
+        let designator = *self
             .shared_mapper
             .get(x, y)
             .expect("Pixel not in designator map. This is a bug.");
+        if self.report_unused_writes && designator.gpio_word.is_none() {
+            eprintln!("Write to pixel ({x}, {y}), which is not mapped to the matrix.");
+        }
+        designator
+    }
+
+    /// Report writes to pixels that are not mapped to the matrix on stderr instead of silently discarding them,
+    /// e.g. to find mistakes in the geometry of a pixel mapper during bring-up.
+    pub fn set_report_unused_writes(&mut self, report: bool) {
+        self.report_unused_writes = report;
+    }
+
+    /// The brightness level of a pixel, including the gain of its panel in the chain.
+    fn brightness_at(&self, designator: PixelDesignator) -> u8 {
+        let Some(gpio_word) = designator.gpio_word else {
+            return self.brightness;
+        };
+        if self.chain_gains.is_empty() {
+            return self.brightness;
+        }
+        let column = gpio_word % (self.matrix_cols * K_BIT_PLANES);
+        let panel = column * self.chain_length / self.matrix_cols;
+        let gain = self.chain_gains.get(panel).copied().unwrap_or(100);
+        (u16::from(self.brightness) * u16::from(gain) / 100) as u8
+    }
+
+    /// Set a pixel that is known to be within the canvas.
+    pub(crate) fn set_pixel_unchecked(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
+        let designator = self.designator_for_write(x, y);
+        let color = self
+            .color_lookup
+            .lookup_rgb(self.brightness_at(designator), r, g, b);
+        self.write_pixel([x, y], designator, color);
+    }
+
+    /// Add a color to the current color of a pixel, e.g. for overlapping light sources. The colors are added in
+    /// the linear luminance domain that is shown on the matrix and each channel saturates at full intensity.
+    pub fn add_pixel(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let designator = self.designator_for_write(x, y);
+        let current = self.get_luminance(designator);
+        let added = self
+            .color_lookup
+            .lookup_rgb(self.brightness_at(designator), r, g, b);
+        let max = (1 << K_BIT_PLANES) - 1;
+        let mut color = [0; 3];
+        for ((value, current), added) in color.iter_mut().zip(current).zip(added) {
+            *value = current.saturating_add(added).min(max);
+        }
+        self.write_pixel([x, y], designator, color);
+    }
+
+    /// Composite a layer of premultiplied RGBA pixels onto the canvas with source-over blending. `pixels` holds
+    /// `width * height` pixels in row-major order and its top left pixel is placed at `origin`. Pixels outside
+    /// of the canvas are clipped.
+    ///
+    /// The blending happens on the 8 bit color values, i.e. before the luminance correction, like in most UI
+    /// toolkits.
+    pub fn composite_layer(
+        &mut self,
+        pixels: &[(u8, u8, u8, u8)],
+        width: usize,
+        height: usize,
+        origin: [isize; 2],
+    ) {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "The layer needs to have width * height pixels."
+        );
+        for (index, &(r, g, b, alpha)) in pixels.iter().enumerate() {
+            let x = origin[0] + (index % width) as isize;
+            let y = origin[1] + (index / width) as isize;
+            if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.height() {
+                continue;
+            }
+            let [x, y] = [x as usize, y as usize];
+            if alpha == 255 {
+                self.set_pixel_unchecked(x, y, r, g, b);
+                continue;
+            }
+            if alpha == 0 && r == 0 && g == 0 && b == 0 {
+                continue;
+            }
+            let current = self.get_color(x, y);
+            let blend = |source: u8, destination: u8| {
+                let remaining = u16::from(destination) * u16::from(255 - alpha) / 255;
+                (u16::from(source) + remaining).min(255) as u8
+            };
+            self.set_pixel_unchecked(
+                x,
+                y,
+                blend(r, current[0]),
+                blend(g, current[1]),
+                blend(b, current[2]),
+            );
+        }
+    }
+
+    /// Set a pixel to a color of a palette. Indices outside of the palette are ignored.
+    pub fn set_pixel_indexed(&mut self, x: usize, y: usize, index: usize, palette: &Palette) {
+        if let Some([r, g, b]) = palette.get(index) {
+            self.set_pixel(x, y, r, g, b);
+        }
+    }
+
+    /// Set a pixel to output luminances that were already corrected, e.g. by your own lookup table. The values
+    /// are written to the bit planes as they are, without luminance correction or brightness. Values above
+    /// `2^11 - 1`, the range of the 11 bit planes, saturate.
+    pub fn set_pixel_raw(&mut self, x: usize, y: usize, r: u16, g: u16, b: u16) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let designator = self.designator_for_write(x, y);
+        let max = (1 << K_BIT_PLANES) - 1;
+        self.write_pixel([x, y], designator, [r, g, b].map(|value| value.min(max)));
+    }
+
+    /// Set a pixel from a packed `0x00RRGGBB` color. The highest byte is ignored.
+    pub fn set_pixel_u32(&mut self, x: usize, y: usize, color: u32) {
+        let [_, r, g, b] = color.to_be_bytes();
+        self.set_pixel(x, y, r, g, b);
+    }
+
+    /// Switch the color channels of a pixel fully on or off. With `pwm_bits == 1`, e.g. for high contrast
+    /// text, this only writes the single bit plane that is shown instead of looking up the color.
+    pub fn set_pixel_bool(&mut self, x: usize, y: usize, r: bool, g: bool, b: bool) {
+        if self.pwm_bits != 1 || self.shared_mapper.scale() > 1 {
+            let value = |on: bool| if on { 255 } else { 0 };
+            self.set_pixel(x, y, value(r), value(g), value(b));
+            return;
+        }
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let designator = self.designator_for_write(x, y);
+        let Some(pos_start) = designator.gpio_word else {
+            // non-used pixel marker.
+            return;
+        };
+
+        // Depending on the brightness, full color might not reach the top bit plane.
+        let plane = K_BIT_PLANES - 1;
+        let [full, ..] =
+            self.color_lookup
+                .lookup_rgb(self.brightness_at(designator), 255, 255, 255);
+        let lit = full & (1 << plane) != 0;
+
+        let mut color_bits = 0;
+        if lit && r {
+            color_bits |= designator.r_bit;
+        }
+        if lit && g {
+            color_bits |= designator.g_bit;
+        }
+        if lit && b {
+            color_bits |= designator.b_bit;
+        }
+        let pos = pos_start + self.matrix_cols * plane;
+        self.bitplane_buffer[pos] &= designator.mask;
+        self.bitplane_buffer[pos] |= color_bits;
+    }
+
+    /// Write the output luminance of a visible pixel to all matrix pixels it is shown on.
+    fn write_pixel(&mut self, [x, y]: [usize; 2], designator: PixelDesignator, color: [u16; 3]) {
+        self.write_designator(designator, color);
+        for index in 0..self.shared_mapper.copies(x, y).len() {
+            let copy = self.shared_mapper.copies(x, y)[index];
+            self.write_designator(copy, color);
+        }
+    }
+
+    fn write_designator(&mut self, designator: PixelDesignator, [red, green, blue]: [u16; 3]) {
         let PixelDesignator {
             gpio_word,
             r_bit,
             g_bit,
             b_bit,
             mask: designator_mask,
-        } = *designator;
+        } = designator;
 
         let Some(pos_start) = gpio_word else {
             // non-used pixel marker.
             return;
         };
 
-        let [red, green, blue] = self.color_lookup.lookup_rgb(self.brightness, r, g, b);
-
         let min_bit_plane = K_BIT_PLANES - self.pwm_bits;
 
         (min_bit_plane..K_BIT_PLANES).for_each(|plane| {
-            let pos = pos_start + self.cols * plane;
+            let pos = pos_start + self.matrix_cols * plane;
             let mask = 1 << plane;
             let mut color_bits = 0;
             if (red & mask) != 0 {
@@ -258,6 +833,22 @@ impl Canvas {
     }
 
     pub fn fill(&mut self, r: u8, g: u8, b: u8) {
+        if self.protected_region.is_some() {
+            self.fill_region([0, 0, self.width(), self.height()], r, g, b);
+            return;
+        }
+
+        // Writing whole rows is only correct if every matrix pixel is visible and all sub-panels have the same
+        // LED sequence and brightness. Otherwise, e.g. after a mapper that leaves some pixels unused, go through
+        // the designators of the visible pixels.
+        if !self.shared_mapper.is_one_to_one()
+            || !self.shared_mapper.has_uniform_sequence()
+            || !self.chain_gains.is_empty()
+        {
+            self.fill_mapped(r, g, b);
+            return;
+        }
+
         let designator = self.shared_mapper.get_pixel_designator();
         let PixelDesignator {
             r_bit,
@@ -268,6 +859,10 @@ impl Canvas {
 
         let [red, green, blue] = self.color_lookup.lookup_rgb(self.brightness, r, g, b);
 
+        // The inactive planes are not written below, so clear them to not leave the content of earlier frames
+        // drawn with more PWM bits.
+        self.set_pwm_bits_and_clear(self.pwm_bits);
+
         (K_BIT_PLANES - self.pwm_bits..K_BIT_PLANES).for_each(|b| {
             let mask = 1 << b;
             let mut plane_bits = 0;
@@ -286,6 +881,366 @@ impl Canvas {
         });
     }
 
+    /// Set every pixel to the color returned by `shader` for its normalized coordinates `(u, v)`, where `u`
+    /// runs from left to right and `v` from top to bottom, both from 0 to 1. The coordinates are those of the
+    /// pixel centers, so effects look the same on any canvas size.
+    pub fn shade(&mut self, mut shader: impl FnMut(f32, f32) -> (u8, u8, u8)) {
+        let (width, height) = self.dimensions();
+        for y in 0..height {
+            let v = (y as f32 + 0.5) / height as f32;
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                let (r, g, b) = shader(u, v);
+                self.set_pixel_unchecked(x, y, r, g, b);
+            }
+        }
+    }
+
+    /// Draw a ruler to check the configured rows against the panel, the first step when bringing up an unknown
+    /// panel. Each row is a line from the left edge that gets longer from top to bottom, so the canvas shows a
+    /// single triangle with the last row spanning the full width. The rows are red, green and blue in turn and
+    /// every eighth row is white, to count them.
+    ///
+    /// If the panel shows two triangles stacked on top of each other, it has more rows than configured. If it
+    /// shows only the narrow part of the triangle, it has fewer.
+    pub fn draw_row_ruler(&mut self) {
+        const COLORS: [(u8, u8, u8); 3] = [(255, 0, 0), (0, 255, 0), (0, 0, 255)];
+        let (width, height) = self.dimensions();
+        self.clear();
+        for y in 0..height {
+            let length = ((y + 1) * width).div_ceil(height);
+            let (r, g, b) = if y % 8 == 0 {
+                (255, 255, 255)
+            } else {
+                COLORS[y % COLORS.len()]
+            };
+            self.fill_region([0, y, length, 1], r, g, b);
+        }
+    }
+
+    /// Set all pixels to black. Unlike `fill(0, 0, 0)`, this is correct regardless of the pixel mapping, since
+    /// black has all color bits off.
+    pub fn clear(&mut self) {
+        if self.protected_region.is_some() {
+            self.fill_region([0, 0, self.width(), self.height()], 0, 0, 0);
+            return;
+        }
+        self.bitplane_buffer.fill(0);
+    }
+
+    /// Turn off all pixels, including the protected region, e.g. to blank the display.
+    pub(crate) fn blank(&mut self) {
+        self.bitplane_buffer.fill(0);
+    }
+
+    /// Fill the pixels of the region `[x, y, width, height]` with a color, except for protected ones. The part
+    /// of the region outside of the canvas is ignored.
+    pub fn fill_region(&mut self, [x, y, width, height]: [usize; 4], r: u8, g: u8, b: u8) {
+        let x_end = x.saturating_add(width).min(self.width());
+        let y_end = y.saturating_add(height).min(self.height());
+        for y in y..y_end {
+            for x in x..x_end {
+                if self.is_protected(x, y) {
+                    continue;
+                }
+                let designator = *self
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                let color = self
+                    .color_lookup
+                    .lookup_rgb(self.brightness_at(designator), r, g, b);
+                self.write_pixel([x, y], designator, color);
+            }
+        }
+    }
+
+    /// Protect the region `[x, y, width, height]` from [`Canvas::fill`], [`Canvas::clear`] and
+    /// [`Canvas::fill_region`], e.g. to keep a static logo while clearing the rest of the canvas every frame.
+    /// Drawing single pixels is not affected. `None` removes the protection.
+    pub fn set_protected_region(&mut self, region: Option<[usize; 4]>) {
+        self.protected_region = region;
+    }
+
+    fn is_protected(&self, x: usize, y: usize) -> bool {
+        self.protected_region
+            .is_some_and(|[region_x, region_y, width, height]| {
+                (region_x..region_x.saturating_add(width)).contains(&x)
+                    && (region_y..region_y.saturating_add(height)).contains(&y)
+            })
+    }
+
+    fn fill_mapped(&mut self, r: u8, g: u8, b: u8) {
+        // Pixels that are not visible stay dark.
+        self.bitplane_buffer.fill(0);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let designator = *self
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                let color = self
+                    .color_lookup
+                    .lookup_rgb(self.brightness_at(designator), r, g, b);
+                self.write_pixel([x, y], designator, color);
+            }
+        }
+    }
+
+    /// Read back the output luminance of a pixel from the bit planes.
+    fn get_luminance(&self, designator: PixelDesignator) -> [u16; 3] {
+        let Some(pos_start) = designator.gpio_word else {
+            return [0, 0, 0];
+        };
+        let mut color = [0u16; 3];
+        (K_BIT_PLANES - self.pwm_bits..K_BIT_PLANES).for_each(|plane| {
+            let bits = self.bitplane_buffer[pos_start + self.matrix_cols * plane];
+            let channel_bits = [designator.r_bit, designator.g_bit, designator.b_bit];
+            for (value, channel_bit) in color.iter_mut().zip(channel_bits) {
+                if bits & channel_bit != 0 {
+                    *value |= 1 << plane;
+                }
+            }
+        });
+        color
+    }
+
+    /// Swap the content of two visible pixels.
+    fn swap_pixels(&mut self, a: [usize; 2], b: [usize; 2]) {
+        let designator_a = *self
+            .shared_mapper
+            .get(a[0], a[1])
+            .expect("Pixel not in designator map. This is a bug.");
+        let designator_b = *self
+            .shared_mapper
+            .get(b[0], b[1])
+            .expect("Pixel not in designator map. This is a bug.");
+        let luminance_a = self.get_luminance(designator_a);
+        let luminance_b = self.get_luminance(designator_b);
+        self.write_pixel(a, designator_a, luminance_b);
+        self.write_pixel(b, designator_b, luminance_a);
+    }
+
+    /// Mirror the current content of the canvas, either horizontally (left to right) or vertically (top to
+    /// bottom). Unlike the `Mirror` pixel mapper, this transforms what has been drawn so far.
+    pub fn mirror(&mut self, horizontal: bool) {
+        let [width, height] = [self.width(), self.height()];
+        if horizontal {
+            for y in 0..height {
+                for x in 0..width / 2 {
+                    self.swap_pixels([x, y], [width - 1 - x, y]);
+                }
+            }
+        } else {
+            for y in 0..height / 2 {
+                for x in 0..width {
+                    self.swap_pixels([x, y], [x, height - 1 - y]);
+                }
+            }
+        }
+    }
+
+    /// Rotate the current content of the canvas by 180 degrees.
+    pub fn rotate_180(&mut self) {
+        self.mirror(true);
+        self.mirror(false);
+    }
+
+    /// Read back the color of a visible pixel as it would be passed to [`Canvas::set_pixel`]. The lower bits
+    /// that are cut off by the PWM bits and the luminance correction are lost.
+    pub(crate) fn get_color(&self, x: usize, y: usize) -> [u8; 3] {
+        let designator = *self
+            .shared_mapper
+            .get(x, y)
+            .expect("Pixel not in designator map. This is a bug.");
+        let brightness = self.brightness_at(designator);
+        self.get_luminance(designator)
+            .map(|l| self.color_lookup.inverse_lookup(brightness, l))
+    }
+
+    /// Get the GPIO word in the bit plane buffer that each visible pixel was mapped to, as `(x, y, gpio_word)`
+    /// in row-major order. `None` marks pixels that are not shown on the matrix. This is meant for debugging
+    /// pixel mappers, e.g. by comparing the output with the expected mapping.
+    #[must_use]
+    pub fn dump_mapping(&self) -> Vec<(usize, usize, Option<usize>)> {
+        let mut mapping = Vec::with_capacity(self.width() * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let designator = self
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                mapping.push((x, y, designator.gpio_word));
+            }
+        }
+        mapping
+    }
+
+    /// Count the visible pixels by their luma, e.g. to adjust the brightness to the content. The luma range
+    /// 0..=255 is split into `N` bins of equal width, so `N = 2` separates dark from bright pixels. Pixels that are
+    /// not shown on the matrix are not counted.
+    #[must_use]
+    pub fn luminance_histogram<const N: usize>(&self) -> [u32; N] {
+        assert!(N > 0, "The histogram needs at least one bin.");
+        let mut histogram = [0; N];
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let designator = *self
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                if designator.gpio_word.is_none() {
+                    continue;
+                }
+                let [r, g, b] = self.get_color(x, y);
+                // Rec. 601 luma, scaled by 1000.
+                let luma = 299 * usize::from(r) + 587 * usize::from(g) + 114 * usize::from(b);
+                histogram[luma * N / (256 * 1000)] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Create an off-screen canvas with the panel geometry of `config`, without any pixel mappers. This can
+    /// e.g. be used as a larger canvas to render into before [`Canvas::downsample_from`].
+    #[must_use]
+    pub fn for_config(config: &RGBMatrixConfig) -> Self {
+        let pixel_designator = PixelDesignator::new(&config.hardware_mapping, config.led_sequence);
+        let width = config.cols * config.chain_length;
+        let height = config.rows * config.parallel;
+        let shared_mapper = PixelDesignatorMap::new(pixel_designator, width, height, config);
+        Self::new(config, shared_mapper, [config.rows, config.cols])
+    }
+
+    /// Fill the canvas with a downsampled copy of `src`, e.g. to anti-alias content that was rendered at a
+    /// multiple of the resolution. Each pixel becomes the average color of a `factor` x `factor` block of
+    /// `src`. Blocks that are not entirely inside `src` are skipped.
+    pub fn downsample_from(&mut self, src: &Canvas, factor: usize) {
+        assert!(factor > 0, "The downsampling factor needs to be positive.");
+        let width = self.width().min(src.width() / factor);
+        let height = self.height().min(src.height() / factor);
+        let block_size = (factor * factor) as u32;
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for src_y in y * factor..(y + 1) * factor {
+                    for src_x in x * factor..(x + 1) * factor {
+                        let color = src.get_color(src_x, src_y);
+                        for (sum, value) in sum.iter_mut().zip(color) {
+                            *sum += u32::from(value);
+                        }
+                    }
+                }
+                let [r, g, b] = sum.map(|sum| ((sum + block_size / 2) / block_size) as u8);
+                self.set_pixel_unchecked(x, y, r, g, b);
+            }
+        }
+    }
+
+    /// Set the pixels from row-major RGB bytes, e.g. a decoded video frame of the canvas' size. Surplus bytes are
+    /// ignored, and pixels beyond the end of the bytes keep their color.
+    pub fn set_rgb_bytes(&mut self, bytes: &[u8]) {
+        let width = self.width();
+        for (index, rgb) in bytes
+            .chunks_exact(3)
+            .take(width * self.height())
+            .enumerate()
+        {
+            self.set_pixel_unchecked(index % width, index / width, rgb[0], rgb[1], rgb[2]);
+        }
+    }
+
+    /// Copy the frame of another canvas of the same matrix, without reallocating, so that
+    /// [`Canvas::to_rgb_bytes`] decodes it like the other canvas.
+    pub(crate) fn copy_frame_from(&mut self, other: &Canvas) {
+        self.bitplane_buffer.copy_from_slice(&other.bitplane_buffer);
+        self.pwm_bits = other.pwm_bits;
+        if self.shared_mapper.led_sequences != other.shared_mapper.led_sequences {
+            self.shared_mapper.clone_from(&other.shared_mapper);
+        }
+    }
+
+    /// Decode the bit planes into row-major RGB bytes of the visible pixels, as they are shown on the matrix.
+    pub(crate) fn to_rgb_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.width() * self.height() * 3);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let designator = *self
+                    .shared_mapper
+                    .get(x, y)
+                    .expect("Pixel not in designator map. This is a bug.");
+                let luminance = self.get_luminance(designator);
+                bytes.extend(luminance.map(|l| self.color_lookup.inverse_lookup(255, l)));
+            }
+        }
+        bytes
+    }
+
+    /// Like [`Canvas::set_pixel`], but silently discards negative coordinates.
+    pub(crate) fn set_pixel_clipped(&mut self, x: isize, y: isize, r: u8, g: u8, b: u8) {
+        if x < 0 || y < 0 {
+            return;
+        }
+        self.set_pixel(x as usize, y as usize, r, g, b);
+    }
+
+    /// Draw a line from `start` to `end` (both inclusive) using Bresenham's algorithm. Pixels outside of the
+    /// canvas are discarded.
+    pub fn draw_line(&mut self, start: [isize; 2], end: [isize; 2], r: u8, g: u8, b: u8) {
+        let [mut x, mut y] = start;
+        let [x1, y1] = end;
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel_clipped(x, y, r, g, b);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draw the outline of a circle around `center` using the midpoint algorithm. Pixels outside of the canvas
+    /// are discarded.
+    pub fn draw_circle(&mut self, center: [isize; 2], radius: usize, r: u8, g: u8, b: u8) {
+        let [cx, cy] = center;
+        let mut x = radius as isize;
+        let mut y = 0;
+        let mut error = 1 - x;
+        while x >= y {
+            for [px, py] in [
+                [x, y],
+                [y, x],
+                [-y, x],
+                [-x, y],
+                [-x, -y],
+                [-y, -x],
+                [y, -x],
+                [x, -y],
+            ] {
+                self.set_pixel_clipped(cx + px, cy + py, r, g, b);
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
     pub(crate) fn dump_to_matrix(
         &self,
         gpio: &mut Gpio,
@@ -297,39 +1252,41 @@ impl Canvas {
         // Depending on if we do dithering, we might not always show the lowest bits.
         let start_bit = (K_BIT_PLANES - self.pwm_bits).max(pwm_low_bit);
 
-        let half_double = self.double_rows / 2;
-        for row_loop in 0..self.double_rows {
-            let d_row = if self.interlaced {
-                if row_loop < half_double {
-                    2 * row_loop
-                } else {
-                    2 * (row_loop - half_double) + 1
-                }
-            } else {
-                row_loop
-            };
-
+        for &d_row in &self.row_order {
             // Rows can't be switched very quickly without ghosting, so we do the
             // full PWM of one row before switching rows.
             for b in start_bit..K_BIT_PLANES {
                 // While the output enable is still on, we can already clock in the next data.
                 let row = self.row_at(d_row, 0, b);
-                row.iter().for_each(|col| {
-                    gpio.write_masked_bits(*col, color_clk_mask); // col + reset clock
-                    gpio.set_bits(hardware_mapping.clock); // Rising edge: clock color in.
-                });
+                if self.clock_falling_edge {
+                    row.iter().for_each(|col| {
+                        // col + idle clock
+                        gpio.write_masked_bits(*col | hardware_mapping.clock, color_clk_mask);
+                        gpio.clear_bits(hardware_mapping.clock); // Falling edge: clock color in.
+                    });
 
-                gpio.clear_bits(color_clk_mask); // clock back to normal.
+                    // clock back to idle.
+                    gpio.write_masked_bits(hardware_mapping.clock, color_clk_mask);
+                } else {
+                    row.iter().for_each(|col| {
+                        gpio.write_masked_bits(*col, color_clk_mask); // col + reset clock
+                        gpio.set_bits(hardware_mapping.clock); // Rising edge: clock color in.
+                    });
+
+                    gpio.clear_bits(color_clk_mask); // clock back to normal.
+                }
 
                 // OE of the previous row-data must be finished before strobe.
                 gpio.wait_pulse_finished();
 
                 // Setting address and strobing needs to happen in dark time.
+                gpio.begin_blanking();
                 row_setter.set_row_address(gpio, d_row);
 
                 // Strobe in the previously clocked in row.
                 gpio.set_bits(hardware_mapping.strobe);
                 gpio.clear_bits(hardware_mapping.strobe);
+                gpio.end_blanking();
 
                 // Now switch on for the sleep time necessary for that bit-plane.
                 gpio.send_pulse(b);
@@ -343,9 +1300,110 @@ impl Canvas {
         self.pwm_bits = pwm_bits;
     }
 
+    /// Like [`Canvas::set_pwm_bits`], but also clears the bit planes below the new PWM bits. Otherwise, pixels
+    /// that are not redrawn keep their lower bits from before, which reappear when the PWM bits are raised
+    /// again, e.g. after temporarily lowering them for a higher refresh rate. The PWM bits are clamped to the
+    /// valid range of 1 to 11.
+    pub fn set_pwm_bits_and_clear(&mut self, pwm_bits: usize) {
+        self.set_pwm_bits(pwm_bits.clamp(1, K_BIT_PLANES));
+        let inactive_words = (K_BIT_PLANES - self.pwm_bits) * self.matrix_cols;
+        self.bitplane_buffer
+            .chunks_exact_mut(self.matrix_cols * K_BIT_PLANES)
+            .for_each(|double_row| double_row[..inactive_words].fill(0));
+    }
+
+    /// Change the LED sequence of all panels without creating a new matrix, e.g. for a tool that finds the
+    /// sequence of unknown panels interactively. This also replaces the sequence of
+    /// [`RGBMatrixConfig::led_sequence_lower`]. Pixels that were drawn before keep their previous color
+    /// assignment, so redraw the canvas afterwards.
+    pub fn set_led_sequence(&mut self, sequence: LedSequence) {
+        if self.shared_mapper.led_sequence() != sequence
+            || !self.shared_mapper.has_uniform_sequence()
+        {
+            self.shared_mapper.set_led_sequence(sequence);
+        }
+    }
+
     /// Set the canvas' brightness in percent.
     pub fn set_brightness(&mut self, brightness: u8) {
-        self.brightness = brightness.clamp(1, 100);
+        self.brightness = brightness_level(brightness.max(1));
+    }
+
+    /// Set the canvas' brightness in 256 levels, from 0 (off) to 255 (full brightness), e.g. for smooth fades.
+    pub fn set_brightness_level(&mut self, level: u8) {
+        self.brightness = level;
+    }
+
+    /// Get a copy of the canvas with the brightness in percent changed, e.g. to compare settings without
+    /// changing this canvas. Like [`Canvas::set_brightness`], this only affects pixels drawn afterwards. Cloning
+    /// copies the whole bit plane buffer, so don't do this every frame.
+    #[must_use]
+    pub fn with_brightness(&self, brightness: u8) -> Self {
+        let mut canvas = self.clone();
+        canvas.set_brightness(brightness);
+        canvas
+    }
+
+    /// Get a copy of the canvas with the PWM bits changed, e.g. to compare settings without changing this
+    /// canvas. Cloning copies the whole bit plane buffer, so don't do this every frame. The PWM bits are clamped
+    /// to the valid range of 1 to 11.
+    #[must_use]
+    pub fn with_pwm_bits(&self, pwm_bits: usize) -> Self {
+        let mut canvas = self.clone();
+        canvas.set_pwm_bits(pwm_bits.clamp(1, K_BIT_PLANES));
+        canvas
+    }
+
+    /// The brightness level from 0 (off) to 255.
+    pub(crate) fn brightness_level(&self) -> u8 {
+        self.brightness
+    }
+
+    pub(crate) fn pwm_bits(&self) -> usize {
+        self.pwm_bits
+    }
+
+    pub(crate) fn chain_gains(&self) -> &[u8] {
+        &self.chain_gains
+    }
+
+    /// Draw an overlay over the content, e.g. right before the canvas is shown. The content without the overlay
+    /// is kept in `backup` for [`Canvas::remove_overlay`].
+    pub(crate) fn apply_overlay(&mut self, overlay: &Overlay, backup: &mut Vec<u32>) {
+        backup.clone_from(&self.bitplane_buffer);
+        for ((word, overlay_word), mask) in self
+            .bitplane_buffer
+            .iter_mut()
+            .zip(&overlay.bitplane_buffer)
+            .zip(&overlay.mask)
+        {
+            *word = (*word & !mask) | (overlay_word & mask);
+        }
+    }
+
+    /// Restore the content from before [`Canvas::apply_overlay`].
+    pub(crate) fn remove_overlay(&mut self, backup: &mut Vec<u32>) {
+        std::mem::swap(&mut self.bitplane_buffer, backup);
+    }
+
+    /// Apply the display parameters set on the matrix. Changing the PWM bits clears the bit planes that are no
+    /// longer shown, see [`Canvas::set_pwm_bits_and_clear`].
+    pub(crate) fn apply_display_settings(&mut self, settings: &DisplaySettings) {
+        if let Some(brightness) = settings.brightness {
+            self.brightness = brightness;
+        }
+        if let Some(pwm_bits) = settings
+            .pwm_bits
+            .filter(|&pwm_bits| pwm_bits != self.pwm_bits)
+        {
+            self.set_pwm_bits_and_clear(pwm_bits);
+        }
+        if let Some(chain_gains) = &settings.chain_gains {
+            self.chain_gains.clone_from(chain_gains);
+        }
+        if let Some(led_sequence) = settings.led_sequence {
+            self.set_led_sequence(led_sequence);
+        }
     }
 }
 
@@ -393,8 +1451,437 @@ pub mod embedded_graphics_support {
         }
 
         fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+            if color == Rgb888::BLACK {
+                Canvas::clear(self);
+                return Ok(());
+            }
             self.fill(color.r(), color.g(), color.b());
             Ok(())
         }
     }
 }
+
+#[cfg(feature = "image")]
+pub mod image_support {
+    use super::Canvas;
+    use image::{imageops::FilterType, RgbImage};
+
+    impl Canvas {
+        /// Draw an image of the `image` crate with its top left corner at `origin`. Pixels outside of the
+        /// canvas are discarded.
+        pub fn draw_image(&mut self, image: &RgbImage, origin: [isize; 2]) {
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let [r, g, b] = pixel.0;
+                self.set_pixel_clipped(origin[0] + x as isize, origin[1] + y as isize, r, g, b);
+            }
+        }
+
+        /// Draw an image of the `image` crate scaled to fit the canvas while keeping its aspect ratio, and
+        /// centered.
+        pub fn draw_image_fit(&mut self, image: &RgbImage, filter: FilterType) {
+            let (width, height) = self.dimensions();
+            let scale = f64::min(
+                width as f64 / image.width().max(1) as f64,
+                height as f64 / image.height().max(1) as f64,
+            );
+            let scaled_width = ((image.width() as f64 * scale).round() as u32).max(1);
+            let scaled_height = ((image.height() as f64 * scale).round() as u32).max(1);
+            let scaled = image::imageops::resize(image, scaled_width, scaled_height, filter);
+            let origin = [
+                (width as isize - scaled_width as isize) / 2,
+                (height as isize - scaled_height as isize) / 2,
+            ];
+            self.draw_image(&scaled, origin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Canvas, DisplaySettings, LedSequence, OutOfBounds, Overlay, PixelDesignator,
+        PixelDesignatorMap, RowOrder,
+    };
+    use crate::{config::K_BIT_PLANES, RGBMatrixConfig};
+
+    #[test]
+    fn test_designator_map_40_rows() {
+        let config = RGBMatrixConfig {
+            rows: 40,
+            cols: 80,
+            parallel: 2,
+            hardware_mapping: crate::HardwareMapping::regular(),
+            ..Default::default()
+        };
+        let pixel_designator = PixelDesignator::new(&config.hardware_mapping, config.led_sequence);
+        let height = config.rows * config.parallel;
+        let map = PixelDesignatorMap::new(pixel_designator, config.cols, height, &config);
+
+        let double_rows = config.double_rows();
+        let buffer_len = double_rows * config.cols * K_BIT_PLANES;
+        let mut used = vec![0u32; buffer_len];
+        for y in 0..height {
+            for x in 0..config.cols {
+                let designator = map.get(x, y).unwrap();
+                let gpio_word = designator.gpio_word.unwrap();
+                assert!(gpio_word < buffer_len);
+                assert_eq!(
+                    gpio_word,
+                    (y % double_rows) * config.cols * K_BIT_PLANES + x
+                );
+
+                // Each pixel drives its own bits of the GPIO word.
+                let bits = designator.r_bit | designator.g_bit | designator.b_bit;
+                assert_eq!(used[gpio_word] & bits, 0);
+                used[gpio_word] |= bits;
+
+                // The upper half of each panel is driven by the first set of color bits.
+                let color_bits = config.hardware_mapping.panels.color_bits[y / config.rows];
+                let expected_red = if y % config.rows < double_rows {
+                    color_bits.r1
+                } else {
+                    color_bits.r2
+                };
+                assert_eq!(designator.r_bit, expected_red);
+            }
+        }
+    }
+
+    #[test]
+    fn test_downsample_from() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 16,
+            ..Default::default()
+        };
+        let scratch_config = RGBMatrixConfig {
+            rows: 32,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut scratch = Canvas::for_config(&scratch_config);
+        scratch.set_pixel(2, 2, 255, 255, 255);
+        scratch.set_pixel(3, 3, 255, 255, 255);
+        let mut canvas = Canvas::for_config(&config);
+        canvas.downsample_from(&scratch, 2);
+
+        let [r, g, b] = canvas.get_color(1, 1);
+        assert!(r.abs_diff(128) <= 2 && r == g && g == b);
+        assert_eq!(canvas.get_color(0, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_protected_region() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 16,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.fill(255, 0, 0);
+        canvas.set_protected_region(Some([2, 2, 4, 4]));
+        canvas.clear();
+        assert_eq!(canvas.get_color(2, 2), [255, 0, 0]);
+        assert_eq!(canvas.get_color(5, 5), [255, 0, 0]);
+        assert_eq!(canvas.get_color(6, 5), [0, 0, 0]);
+
+        canvas.set_protected_region(None);
+        canvas.clear();
+        assert_eq!(canvas.get_color(2, 2), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_set_pwm_bits_and_clear() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 16,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.fill(255, 255, 255);
+        canvas.set_pwm_bits(11);
+        let designator = *canvas.shared_mapper.get(3, 3).unwrap();
+        let full = canvas.get_luminance(designator);
+
+        canvas.set_pwm_bits(4);
+        canvas.set_pwm_bits(11);
+        assert_eq!(canvas.get_luminance(designator), full);
+
+        canvas.set_pwm_bits_and_clear(4);
+        canvas.set_pwm_bits(11);
+        let lower_planes = (1 << (K_BIT_PLANES - 4)) - 1;
+        assert_eq!(
+            canvas.get_luminance(designator),
+            full.map(|l| l & !lower_planes)
+        );
+
+        canvas.set_pwm_bits_and_clear(12);
+        assert_eq!(canvas.pwm_bits(), K_BIT_PLANES);
+        canvas.set_pwm_bits_and_clear(0);
+        assert_eq!(canvas.pwm_bits(), 1);
+        assert_eq!(canvas.with_pwm_bits(12).pwm_bits(), K_BIT_PLANES);
+    }
+
+    #[test]
+    fn test_fill_after_lowering_pwm_bits() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.fill(255, 255, 255);
+        canvas.set_pwm_bits(4);
+        canvas.fill(255, 255, 255);
+
+        // Raising the PWM bits again shows no stale planes of the first fill.
+        canvas.set_pwm_bits(11);
+        let inactive_words = (K_BIT_PLANES - 4) * canvas.matrix_cols;
+        for double_row in canvas
+            .bitplane_buffer
+            .chunks_exact(canvas.matrix_cols * K_BIT_PLANES)
+        {
+            assert!(double_row[..inactive_words].iter().all(|&word| word == 0));
+            assert!(double_row[inactive_words..].iter().all(|&word| word != 0));
+        }
+    }
+
+    #[test]
+    fn test_set_led_sequence() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            parallel: 2,
+            led_sequence: LedSequence::Gbr,
+            led_sequence_lower: Some(LedSequence::Brg),
+            hardware_mapping: crate::HardwareMapping::regular(),
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.set_led_sequence(LedSequence::Rgb);
+
+        // The designators match those of a canvas created with the new sequence.
+        let expected = Canvas::for_config(&RGBMatrixConfig {
+            led_sequence: LedSequence::Rgb,
+            led_sequence_lower: None,
+            ..config
+        });
+        assert!(canvas.shared_mapper.has_uniform_sequence());
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let actual = canvas.shared_mapper.get(x, y).unwrap();
+                let expected = expected.shared_mapper.get(x, y).unwrap();
+                assert_eq!(
+                    [actual.r_bit, actual.g_bit, actual.b_bit, actual.mask],
+                    [
+                        expected.r_bit,
+                        expected.g_bit,
+                        expected.b_bit,
+                        expected.mask
+                    ]
+                );
+            }
+        }
+        let actual = canvas.shared_mapper.get_pixel_designator();
+        let expected = expected.shared_mapper.get_pixel_designator();
+        assert_eq!(
+            [actual.r_bit, actual.g_bit, actual.b_bit],
+            [expected.r_bit, expected.g_bit, expected.b_bit]
+        );
+    }
+
+    #[test]
+    fn test_row_order_permutation() {
+        for row_order in [RowOrder::Sequential, RowOrder::Interlaced] {
+            for double_rows in 1..=64 {
+                let rows = row_order.rows(double_rows).unwrap();
+                let mut sorted = rows.clone();
+                sorted.sort_unstable();
+                assert!(
+                    sorted.into_iter().eq(0..double_rows),
+                    "{row_order:?} with {double_rows} double rows: {rows:?}"
+                );
+            }
+        }
+        assert_eq!(RowOrder::Interlaced.rows(5), Some(vec![0, 2, 4, 1, 3]));
+
+        // The canvas refreshes the rows in the configured order.
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 32,
+            interlaced: true,
+            ..Default::default()
+        };
+        let canvas = Canvas::for_config(&config);
+        assert_eq!(Some(canvas.row_order), RowOrder::Interlaced.rows(16));
+    }
+
+    #[test]
+    fn test_custom_row_order() {
+        let row_order: RowOrder = "0,2,1,3".parse().unwrap();
+        assert_eq!(row_order.rows(4), Some(vec![0, 2, 1, 3]));
+        assert_eq!(row_order.rows(5), None);
+        let row_order: RowOrder = "0,2,2,3".parse().unwrap();
+        assert_eq!(row_order.rows(4), None);
+        assert!("0,x".parse::<RowOrder>().is_err());
+        assert_eq!(
+            "Interlaced".parse::<RowOrder>().unwrap(),
+            RowOrder::Interlaced
+        );
+    }
+
+    #[test]
+    fn test_try_set_pixel() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        assert!(canvas.try_set_pixel(31, 15, 255, 0, 0).is_ok());
+        assert_eq!(canvas.get_color(31, 15), [255, 0, 0]);
+        assert_eq!(
+            canvas.try_set_pixel(32, 3, 255, 0, 0),
+            Err(OutOfBounds {
+                x: 32,
+                y: 3,
+                width: 32,
+                height: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chain_gains() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 16,
+            chain_length: 3,
+            chain_gains: Some("100,50".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.fill(255, 255, 255);
+        let luminance =
+            |canvas: &Canvas, x| canvas.get_luminance(*canvas.shared_mapper.get(x, 0).unwrap());
+        let [full, ..] = luminance(&canvas, 15);
+        let [dimmed, ..] = luminance(&canvas, 16);
+        assert!(dimmed < full);
+        assert_eq!(luminance(&canvas, 32), luminance(&canvas, 0));
+        assert_eq!(canvas.get_color(16, 0), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_apply_display_settings() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            chain_length: 2,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        let unchanged = canvas.clone();
+        canvas.apply_display_settings(&DisplaySettings::default());
+        assert_eq!(canvas.brightness_level(), unchanged.brightness_level());
+        assert_eq!(canvas.pwm_bits(), unchanged.pwm_bits());
+
+        canvas.fill(255, 255, 255);
+        canvas.apply_display_settings(&DisplaySettings {
+            brightness: Some(128),
+            pwm_bits: Some(4),
+            chain_gains: Some(vec![100, 50]),
+            led_sequence: None,
+        });
+        assert_eq!(canvas.brightness_level(), 128);
+        assert_eq!(canvas.pwm_bits(), 4);
+        assert_eq!(canvas.chain_gains(), [100, 50]);
+        // The planes below the new PWM bits were cleared.
+        canvas.set_pwm_bits(11);
+        assert!(
+            canvas.bitplane_buffer[..(K_BIT_PLANES - 4) * canvas.matrix_cols]
+                .iter()
+                .all(|&word| word == 0)
+        );
+    }
+
+    #[test]
+    fn test_pixel_scale() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let pixel_designator = PixelDesignator::new(&config.hardware_mapping, config.led_sequence);
+        let full_mapper = PixelDesignatorMap::new(pixel_designator, 32, 16, &config);
+        let mut canvas = Canvas::new(&config, full_mapper.upscaled(2), [16, 32]);
+        assert_eq!(canvas.dimensions(), (16, 8));
+
+        // Each canvas pixel is written to a block of 2 x 2 matrix pixels.
+        let mut expected = Canvas::for_config(&config);
+        canvas.set_pixel(3, 5, 10, 200, 30);
+        canvas.set_pixel_bool(15, 7, true, false, true);
+        for [x, y] in [[6, 10], [7, 10], [6, 11], [7, 11]] {
+            expected.set_pixel(x, y, 10, 200, 30);
+        }
+        for [x, y] in [[30, 14], [31, 14], [30, 15], [31, 15]] {
+            expected.set_pixel(x, y, 255, 0, 255);
+        }
+        assert_eq!(canvas.bitplane_buffer, expected.bitplane_buffer);
+        assert_eq!(canvas.get_color(3, 5), expected.get_color(6, 10));
+
+        canvas.fill(1, 2, 3);
+        expected.fill(1, 2, 3);
+        assert_eq!(canvas.bitplane_buffer, expected.bitplane_buffer);
+    }
+
+    #[test]
+    fn test_draw_row_ruler() {
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 64,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.draw_row_ruler();
+        let length = |y: usize| {
+            (0..canvas.width())
+                .take_while(|&x| canvas.get_color(x, y) != [0, 0, 0])
+                .count()
+        };
+        assert_eq!(length(0), 2);
+        assert_eq!(length(31), 64);
+        assert!((1..32).all(|y| length(y) > length(y - 1)));
+        assert_eq!(canvas.get_color(0, 8), [255, 255, 255]);
+        assert_eq!(canvas.get_color(0, 9), [255, 0, 0]);
+        assert_eq!(canvas.get_color(1, 0), [255, 255, 255]);
+        assert_eq!(canvas.get_color(2, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        canvas.fill(0, 0, 255);
+        let mut overlay_canvas = Canvas::for_config(&config);
+        overlay_canvas.set_pixel(3, 4, 255, 0, 0);
+        // The pixel in the other half shares the GPIO words.
+        overlay_canvas.set_pixel(3, 12, 0, 255, 0);
+        let overlay = Overlay::new(&overlay_canvas);
+        assert!(overlay.fits(&canvas));
+
+        let drawn = canvas.bitplane_buffer.clone();
+        let mut backup = Vec::new();
+        canvas.apply_overlay(&overlay, &mut backup);
+        assert_eq!(canvas.get_color(3, 4), [255, 0, 0]);
+        assert_eq!(canvas.get_color(3, 12), [0, 255, 0]);
+        // Black overlay pixels are transparent.
+        assert_eq!(canvas.get_color(4, 4), [0, 0, 255]);
+        assert_eq!(canvas.get_color(3, 5), [0, 0, 255]);
+        canvas.remove_overlay(&mut backup);
+        assert_eq!(canvas.bitplane_buffer, drawn);
+    }
+}
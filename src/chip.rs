@@ -6,6 +6,8 @@ pub enum PiChip {
     BCM2708,
     /// Models 2 and 3
     BCM2709,
+    /// Models 3A+, 3B+ and Compute Modules 3 and 3+
+    BCM2710A1,
     /// Model 4
     BCM2711,
 }
@@ -17,50 +19,152 @@ impl FromStr for PiChip {
         match s.to_uppercase().as_str() {
             "BCM2708" | "BCM2835" => Ok(Self::BCM2708),
             "BCM2709" | "BCM2836" | "BCM2837" => Ok(Self::BCM2709),
+            "BCM2710A1" | "BCM2837B0" => Ok(Self::BCM2710A1),
             "BCM2711" => Ok(Self::BCM2711),
             _ => Err(format!("'{s}' is not a valid chip model.").into()),
         }
     }
 }
 
+/// The fields decoded from a new-style revision code of the board.
+/// See <https://www.raspberrypi.com/documentation/computers/raspberry-pi.html#new-style-revision-codes>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoardInfo {
+    /// The board revision, e.g. 1 for v1.1.
+    pub revision: u8,
+    /// The board type, e.g. `0x0D` for the 3B+. See [`BoardInfo::board_name`].
+    pub board_type: u8,
+    /// The processor, 0 for the BCM2835 up to 3 for the BCM2711.
+    pub processor: u8,
+    /// The manufacturer. See [`BoardInfo::manufacturer_name`].
+    pub manufacturer: u8,
+    /// The memory size in megabytes.
+    pub memory_mb: u32,
+}
+
+impl BoardInfo {
+    /// Decode a new-style revision code. Returns `None` for old-style codes.
+    #[must_use]
+    pub fn from_revision(revision: u32) -> Option<Self> {
+        // Bits: NOQuuuWuFMMMCCCCPPPPTTTTTTTTRRRR
+        //                ^ new-style flag
+        //                 ^^^ memory size
+        //                    ^^^^ manufacturer
+        //                        ^^^^ processor model
+        //                            ^^^^^^^^ board type
+        //                                    ^^^^ revision
+        let new_style = (revision >> 23) & 1 == 1;
+        if !new_style {
+            return None;
+        }
+        Some(Self {
+            revision: (revision & 0xF) as u8,
+            board_type: ((revision >> 4) & 0xFF) as u8,
+            processor: ((revision >> 12) & 0xF) as u8,
+            manufacturer: ((revision >> 16) & 0xF) as u8,
+            memory_mb: 256 << ((revision >> 20) & 0b111),
+        })
+    }
+
+    /// The name of the board type, e.g. "3B+".
+    #[must_use]
+    pub fn board_name(&self) -> Option<&'static str> {
+        let name = match self.board_type {
+            0x00 => "A",
+            0x01 => "B",
+            0x02 => "A+",
+            0x03 => "B+",
+            0x04 => "2B",
+            0x06 => "CM1",
+            0x08 => "3B",
+            0x09 => "Zero",
+            0x0A => "CM3",
+            0x0C => "Zero W",
+            0x0D => "3B+",
+            0x0E => "3A+",
+            0x10 => "CM3+",
+            0x11 => "4B",
+            0x12 => "Zero 2 W",
+            0x13 => "400",
+            0x14 => "CM4",
+            0x15 => "CM4S",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// The name of the manufacturer.
+    #[must_use]
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        let name = match self.manufacturer {
+            0 => "Sony UK",
+            1 => "Egoman",
+            2 | 4 => "Embest",
+            3 => "Sony Japan",
+            5 => "Stadium",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+/// Read the revision code from `/proc/cpuinfo`.
+fn read_revision() -> Option<String> {
+    // https://www.raspberrypi.org/documentation/hardware/raspberrypi/revision-codes/README.md
+    let cpuinfo = read_to_string("/proc/cpuinfo").ok()?;
+    let revision_str = cpuinfo
+        .lines()
+        .find(|line| line.starts_with("Revision"))?
+        .split(' ')
+        .last()?;
+    Some(revision_str.to_string())
+}
+
 impl PiChip {
     /// Try to automatically determine the model.
     #[must_use]
     pub fn determine() -> Option<Self> {
-        // https://www.raspberrypi.org/documentation/hardware/raspberrypi/revision-codes/README.md
-        let cpuinfo = read_to_string("/proc/cpuinfo").ok()?;
-        let revision_str = cpuinfo
-            .lines()
-            .find(|line| line.starts_with("Revision"))?
-            .split(' ')
-            .last()?;
+        let revision_str = read_revision()?;
 
         let old_style = revision_str.len() == 4;
         if old_style {
             return Some(Self::BCM2708);
         }
 
-        let revision = u32::from_str_radix(revision_str, 16).ok()?;
-        // Bits: NOQuuuWuFMMMCCCCPPPPTTTTTTTTRRRR
-        //                       ^^^^ processor model
-        let model_bits = (revision >> 12) & 0b1111;
-        match model_bits {
+        let revision = u32::from_str_radix(&revision_str, 16).ok()?;
+        let Some(board_info) = BoardInfo::from_revision(revision) else {
+            // Old-style codes with the warranty bit set, which are all BCM2835 boards.
+            return Some(Self::BCM2708);
+        };
+        match board_info.processor {
             // BCM2835
             0 => Some(Self::BCM2708),
             // BCM2836
             1 => Some(Self::BCM2709),
-            // BCM2837
-            2 => Some(Self::BCM2709),
+            // BCM2837, but the CM3 (0x0A), 3B+ (0x0D), 3A+ (0x0E) and CM3+ (0x10) are faster and need more
+            // slowdown.
+            2 => match board_info.board_type {
+                0x0A | 0x0D | 0x0E | 0x10 => Some(Self::BCM2710A1),
+                _ => Some(Self::BCM2709),
+            },
             // BCM2711
             3 => Some(Self::BCM2711),
             _ => None,
         }
     }
 
+    /// Read and decode the revision code of the board, e.g. for logging. Returns `None` if it can't be read or
+    /// if the board uses an old-style revision code.
+    #[must_use]
+    pub fn board_info() -> Option<BoardInfo> {
+        let revision = u32::from_str_radix(&read_revision()?, 16).ok()?;
+        BoardInfo::from_revision(revision)
+    }
+
     pub(crate) const fn num_cores(self) -> usize {
         match self {
             PiChip::BCM2708 => 1,
-            PiChip::BCM2709 | PiChip::BCM2711 => 4,
+            PiChip::BCM2709 | PiChip::BCM2710A1 | PiChip::BCM2711 => 4,
         }
     }
 
@@ -68,7 +172,7 @@ impl PiChip {
     pub(crate) const fn get_peripherals_base(self) -> u64 {
         match self {
             PiChip::BCM2708 => 0x2000_0000,
-            PiChip::BCM2709 => 0x3F00_0000,
+            PiChip::BCM2709 | PiChip::BCM2710A1 => 0x3F00_0000,
             PiChip::BCM2711 => 0xFE00_0000,
         }
     }
@@ -76,6 +180,7 @@ impl PiChip {
     pub(crate) fn gpio_slowdown(self) -> u32 {
         match self {
             PiChip::BCM2708 | PiChip::BCM2709 => 1,
+            PiChip::BCM2710A1 => 2,
             PiChip::BCM2711 => 3,
         }
     }
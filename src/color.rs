@@ -1,9 +1,20 @@
+use std::{error::Error, str::FromStr};
+
 use crate::config::K_BIT_PLANES;
 
+/// The number of brightness levels, from off to full brightness.
+const BRIGHTNESS_LEVELS: usize = 256;
+
+/// Convert a brightness in percent to a brightness level.
+pub(crate) fn brightness_level(percent: u8) -> u8 {
+    ((u16::from(percent.min(100)) * 255 + 50) / 100) as u8
+}
+
 // Do CIE1931 luminance correction and scale to output bitplanes
-fn luminance_cie1931(c: u8, brightness: u8) -> u16 {
+fn luminance_cie1931(c: u8, brightness_level: u8) -> u16 {
     let out_factor = ((1 << K_BIT_PLANES) - 1) as f32;
-    let v = f32::from(c) * f32::from(brightness) / 255.0;
+    // Lightness between 0 and 100.
+    let v = f32::from(c) * f32::from(brightness_level) * 100.0 / (255.0 * 255.0);
     (out_factor
         * (if v <= 8.0 {
             v / 902.3
@@ -14,26 +25,113 @@ fn luminance_cie1931(c: u8, brightness: u8) -> u16 {
 
 #[derive(Clone)]
 pub(crate) struct ColorLookup {
-    per_brightness: [[u16; 256]; 100],
+    /// The output luminance of each color value for each brightness level.
+    per_brightness: Vec<[u16; 256]>,
 }
 
 impl ColorLookup {
-    pub(crate) fn new_cie1931() -> Self {
-        let mut per_brightness = [[0; 256]; 100];
+    /// Create the lookup table. Non-black colors with a luminance below `black_level_lift` are raised halfway
+    /// towards it, so that the darkest levels don't vanish in the lowest bit planes.
+    pub(crate) fn new_cie1931(black_level_lift: u16) -> Self {
+        let mut per_brightness = vec![[0; 256]; BRIGHTNESS_LEVELS];
         (0..=255u8).for_each(|c| {
-            (0..100u8).for_each(|b| {
-                per_brightness[usize::from(b)][usize::from(c)] = luminance_cie1931(c, b + 1);
+            (0..=255u8).for_each(|b| {
+                let luminance = luminance_cie1931(c, b);
+                per_brightness[usize::from(b)][usize::from(c)] =
+                    if c > 0 && b > 0 && luminance < black_level_lift {
+                        (black_level_lift + luminance) / 2
+                    } else {
+                        luminance
+                    };
             });
         });
         Self { per_brightness }
     }
 
     pub(crate) fn lookup_rgb(&self, brightness: u8, r: u8, g: u8, b: u8) -> [u16; 3] {
-        let for_brightness = &self.per_brightness[brightness as usize - 1];
+        let for_brightness = &self.per_brightness[brightness as usize];
         [
             for_brightness[r as usize],
             for_brightness[g as usize],
             for_brightness[b as usize],
         ]
     }
+
+    /// Find the 8 bit value that results in the given output luminance at the given brightness.
+    pub(crate) fn inverse_lookup(&self, brightness: u8, luminance: u16) -> u8 {
+        let for_brightness = &self.per_brightness[brightness as usize];
+        let index = for_brightness.partition_point(|value| *value < luminance);
+        index.min(255) as u8
+    }
+}
+
+/// The brightness in percent of each panel along a chain, in the order of the canvas columns, e.g. to make the
+/// panels at the start of a long chain as dim as the ones at the end. Parsed from a comma-separated list, e.g.
+/// `"94,97,100"`. Panels beyond the end of the list have full brightness.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChainGains(pub Vec<u8>);
+
+impl FromStr for ChainGains {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .map(|item| match item.parse::<u8>() {
+                Ok(gain) if gain <= 100 => Ok(gain),
+                _ => Err(format!("'{item}' is not a brightness in percent.").into()),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// A fixed set of colors for indexed content, e.g. pixel art. See [`crate::Canvas::set_pixel_indexed`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+    /// The output luminance of each color at full brightness.
+    luminances: Vec<[u16; 3]>,
+}
+
+impl Palette {
+    /// Create a palette from RGB colors.
+    #[must_use]
+    pub fn new(colors: Vec<[u8; 3]>) -> Self {
+        let luminances = colors
+            .iter()
+            .map(|color| color.map(|c| luminance_cie1931(c, 255)))
+            .collect();
+        Self { colors, luminances }
+    }
+
+    /// The colors of the palette.
+    #[must_use]
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// Get the color at an index.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<[u8; 3]> {
+        self.colors.get(index).copied()
+    }
+
+    /// Find the index of the palette color that looks closest to `rgb` on the matrix. The colors are compared
+    /// after the same luminance correction that is used for displaying them. Returns `None` for an empty palette.
+    #[must_use]
+    pub fn quantize(&self, rgb: [u8; 3]) -> Option<usize> {
+        let target = rgb.map(|c| luminance_cie1931(c, 255));
+        self.luminances
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, luminance)| {
+                luminance
+                    .iter()
+                    .zip(target)
+                    .map(|(&a, b)| (i32::from(a) - i32::from(b)).pow(2))
+                    .sum::<i32>()
+            })
+            .map(|(index, _)| index)
+    }
 }
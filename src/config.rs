@@ -1,24 +1,64 @@
+use std::{ops::RangeInclusive, path::PathBuf};
+
 use argh::FromArgs;
 
 use crate::{
-    canvas::LedSequence, init_sequence::PanelType, multiplex_mapper::MultiplexMapperType,
-    named_pixel_mapper::NamedPixelMapperType, row_address_setter::RowAddressSetterType,
+    canvas::{LedSequence, RowOrder},
+    color::ChainGains,
+    init_sequence::{ChainPanelTypes, PanelType},
+    multiplex_mapper::MultiplexMapperType,
+    named_pixel_mapper::NamedPixelMapperType,
+    panel_profile::PanelProfile,
+    registers::PullMode,
+    rgb_matrix::{MatrixCreationError, RGBMatrix},
+    row_address_setter::RowAddressSetterType,
     HardwareMapping, PiChip,
 };
 
 /// Typically, a Hub75 panel is split in two half displays, so that a 1:16 multiplexing actually multiplexes
-/// over two half displays and gives 32 lines.
+/// over two half displays and gives 32 lines. This is the default for [`RGBMatrixConfig::sub_panels`].
 pub(crate) const SUB_PANELS: usize = 2;
 
+/// The number of sub-panels that can be driven by the color pins of a single chain.
+pub(crate) const SUB_PANELS_PER_CHAIN: usize = 2;
+
+/// The number of rows that can be selected with the address lines A to E.
+pub(crate) const MAX_ADDRESSABLE_ROWS: usize = 32;
+
+/// The largest number of pixels a matrix may have. Far more than any chain of panels can refresh, but small
+/// enough that the buffers fit into memory.
+pub(crate) const MAX_PIXELS: usize = 1 << 20;
+
+/// Waits shorter than this are done by busy-waiting instead of sleeping. This is the default for
+/// [`RGBMatrixConfig::min_sys_sleep_time_us`].
+pub(crate) const MIN_SYS_SLEEP_TIME_US: u64 = 100;
+
+/// The default for [`RGBMatrixConfig::gpio_settle_us`].
+pub(crate) const GPIO_SETTLE_US: u64 = 100;
+
+/// The default for [`RGBMatrixConfig::input_queue_size`].
+pub(crate) const INPUT_QUEUE_SIZE: usize = 16;
+
+/// The default for [`RGBMatrixConfig::framerate_window`].
+pub(crate) const FRAMERATE_WINDOW: usize = 60;
+
+/// The estimated time of a single write to the GPIO registers, for [`RGBMatrixConfig::theoretical_max_refresh`].
+const GPIO_WRITE_NS: f64 = 10.0;
+
+/// The number of consecutive frames that have to miss the refresh rate before the PWM bits are lowered down to
+/// [`RGBMatrixConfig::min_pwm_bits`].
+pub(crate) const SLOW_FRAMES_BEFORE_ADAPTING: usize = 30;
+
 /// Maximum usable bit planes. 11 bits seems to be a sweet spot in which we still get somewhat useful refresh
 /// rate and have good color richness. This is the default setting. However, in low-light situations, we want
 /// to be able to scale down brightness more by having more bits at the bottom.
 pub(crate) const K_BIT_PLANES: usize = 11;
 
 /// Configuration for an RGB matrix panel controller.
-#[derive(FromArgs, Debug, PartialEq, Eq, Hash)]
+#[derive(FromArgs, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RGBMatrixConfig {
-    /// the display wiring e.g. "AdafruitHat" or "AdafruitHatPwm". Default: "AdafruitHatPwm"
+    /// the display wiring e.g. "AdafruitHat" or "AdafruitHatPwm", or the path to a pin configuration file.
+    /// Default: "AdafruitHatPwm"
     #[argh(option, default = "HardwareMapping::adafruit_hat_pwm()")]
     pub hardware_mapping: HardwareMapping,
     /// the number of display rows. Default: 64
@@ -43,6 +83,12 @@ pub struct RGBMatrixConfig {
     /// slightly less CPU and result in a higher refresh rate. Default: 11
     #[argh(option, default = "11")]
     pub pwm_bits: usize,
+    /// the bit plane whose on-time the lowest of the --pwm-bits planes gets, from 0 for the on-time of
+    /// --pwm-lsb-nanoseconds up to 11 minus --pwm-bits. Lower values shift all shown planes to shorter on-times,
+    /// which trades brightness for a higher refresh rate at the same color depth. Default: 11 minus --pwm-bits,
+    /// i.e. the longest on-times
+    #[argh(option)]
+    pub first_bit_plane: Option<usize>,
     /// base time-unit for the on-time in the lowest significant bit in nanoseconds. Lower values will allow
     /// higher frame rate, but will also negatively impact quality in some panels. Good values for full-color
     /// display (pwm_bits=11) are somewhere between 100 and 300. Default: 130
@@ -59,44 +105,502 @@ pub struct RGBMatrixConfig {
     /// interlaced scan mode. Default: false
     #[argh(option, default = "false")]
     pub interlaced: bool,
+    /// the order in which the rows of a sub-panel are refreshed: "Sequential", "Interlaced" or a
+    /// comma-separated list of all rows, e.g. to reduce the rolling bar when filming the panels. Default:
+    /// "Interlaced" with --interlaced, otherwise "Sequential"
+    #[argh(option)]
+    pub row_order: Option<RowOrder>,
     /// the lower bits can be time dithered, i.e. their brightness contribution is achieved by only showing
     /// them some frames (this is possible, because the PWM is implemented as binary code modulation). This
     /// will allow higher refresh rate (or same refresh rate with increased --pwm_lsb_nanoseconds). The
     /// disadvantage could be slightly lower brightness, in particular for longer chains, and higher CPU use.
     /// CPU use is not of concern for Raspberry Pi 2, 3 and 4 (as we run on a dedicated core anyway) but
     /// probably for Raspberry Pi 1 or Pi Zero. Default: 0 (no dithering)
-    #[argh(option, default = "0")]
+    #[argh(option, default = "0", from_str_fn(parse_dither_bits))]
     pub dither_bits: usize,
     /// number of daisy-chained panels. Default: 1
     #[argh(option, default = "1")]
     pub chain_length: usize,
-    /// how many chains to run in parallel. Default: 1
+    /// how many chains to run in parallel. The chains share the clock, strobe, address and output enable lines,
+    /// so their rows are always lit at the same time. Default: 1
     #[argh(option, default = "1")]
     pub parallel: usize,
     /// typically left empty, but some panels need a particular initialization sequence. This can be e.g.
     /// "FM6126A" for that particular panel type.
     #[argh(option)]
     pub panel_type: Option<PanelType>,
+    /// the panel type of each panel in the chain for chains that mix panels, e.g. "FM6126,none,none" if only the
+    /// first panel needs an initialization sequence. Takes precedence over --panel-type.
+    #[argh(option)]
+    pub panel_types: Option<ChainPanelTypes>,
     /// the kind of multiplexing mapper.
     #[argh(option)]
     pub multiplexing: Option<MultiplexMapperType>,
     /// the kind of pixel mapper.
     #[argh(option)]
     pub pixelmapper: Vec<NamedPixelMapperType>,
-    /// the row address setter.
+    /// the row address setter. Use "ShiftRegister:<clock><data>", e.g. "ShiftRegister:CB", for a shift register
+    /// wired to other address lines. Default: "Direct"
     #[argh(option, default = "RowAddressSetterType::Direct")]
     pub row_setter: RowAddressSetterType,
     /// the LED sequence, Default: "RGB"
     #[argh(option, default = "LedSequence::Rgb")]
     pub led_sequence: LedSequence,
+    /// the LED sequence of the sub-panels driven by the second set of color pins (R2, G2, B2), usually the
+    /// lower half, for panels whose halves differ. Default: same as --led-sequence
+    #[argh(option)]
+    pub led_sequence_lower: Option<LedSequence>,
     /// brightness in percent. Default: 100
     #[argh(option, default = "100")]
     pub led_brightness: u8,
+    /// a file or named pipe to which every displayed frame is written as raw RGB bytes, e.g. to pipe it into
+    /// ffmpeg. Frames are dropped if the reader can't keep up.
+    #[argh(option)]
+    pub frame_sink: Option<PathBuf>,
+    /// the CPU core the display update thread is pinned to. Default: the last core
+    #[argh(option)]
+    pub cpu_core: Option<usize>,
+    /// don't print advice on how to improve performance, e.g. the isolcpus suggestion. Default: false
+    #[argh(option, default = "false")]
+    pub quiet: bool,
+    /// blank the panel when the matrix is dropped. Without blanking, the panel keeps the row data it latched
+    /// last, but it isn't refreshed anymore: most panels show one row of the last frame at most, or stay dark.
+    /// Default: true
+    #[argh(option, default = "true")]
+    pub blank_on_drop: bool,
+    /// the number of sub-panels a panel is split into. The sub-panels are scanned simultaneously, each with its
+    /// own set of color pins. Panels with more than two sub-panels use the color pins of the following
+    /// parallel chains. Default: 2
+    #[argh(option, default = "SUB_PANELS")]
+    pub sub_panels: usize,
+    /// waits shorter than this many microseconds are done by busy-waiting, longer ones partially sleep. Raising
+    /// it reduces the CPU load, e.g. on single core boards like the Pi Zero, at the cost of more jitter.
+    /// Default: 100
+    #[argh(option, default = "MIN_SYS_SLEEP_TIME_US")]
+    pub min_sys_sleep_time_us: u64,
+    /// microseconds to wait after switching the pins to outputs before the first frame is clocked in, so that
+    /// the pin multiplexing has settled. Raise this if the first frame is garbled until the next redraw.
+    /// Default: 100
+    #[argh(option, default = "GPIO_SETTLE_US")]
+    pub gpio_settle_us: u64,
+    /// a named bundle of multiplexing, row setter, LED sequence and panel type for a known panel, e.g.
+    /// "p10-outdoor-32x16-halfscan". The settings of the profile take precedence over the individual options.
+    #[argh(option)]
+    pub panel_profile: Option<PanelProfile>,
+    /// the internal pull resistor of the requested input pins: "up", "down" or "off". Default: left unchanged
+    #[argh(option)]
+    pub input_pull: Option<PullMode>,
+    /// the number of GPIO input changes that are queued until they are received. Further changes are held back
+    /// until there is space again, so inputs that are never received don't grow the memory. Default: 16
+    #[argh(option, default = "INPUT_QUEUE_SIZE")]
+    pub input_queue_size: usize,
+    /// the output enable pin of the panels is active high instead of the usual active low. Set this if the
+    /// display shows inverted brightness. Default: false
+    #[argh(option, default = "false")]
+    pub oe_active_high: bool,
+    /// the GPIO of the address line E, overriding the one of the hardware mapping. On the Adafruit HAT, line E
+    /// is wired by hand, by default to GPIO 24.
+    #[argh(option)]
+    pub e_pin: Option<u8>,
+    /// the scan rate of the panels, e.g. 8 for 1:8 scan panels. Usually found on the label. If set, the rows,
+    /// the multiplexing and the row setter are checked against it, and panels that scan fewer rows than their
+    /// address lines suggest default to the "Stripe" multiplexing. Default: not checked
+    #[argh(option)]
+    pub scan: Option<usize>,
+    /// raise the darkest non-black levels so they don't vanish in the lowest bit planes, e.g. with few PWM bits
+    /// or with dithering. Levels whose luminance is below this value, on the scale of 0 to 2047 of the 11 bit
+    /// planes, are lifted halfway towards it. Default: 0 (no lift)
+    #[argh(option, default = "0")]
+    pub black_level_lift: u16,
+    /// take an RGB snapshot of every Nth displayed frame, to be received with `RGBMatrix::receive_preview`, e.g.
+    /// for the preview of a remote control panel. Snapshots are dropped while an older one was not received.
+    /// Default: no snapshots
+    #[argh(option)]
+    pub preview_interval: Option<usize>,
+    /// print a coarse preview of the displayed frames to the terminal every this many milliseconds, using ANSI
+    /// true colors, e.g. to check the content over SSH without looking at the panels. The preview is printed to
+    /// stderr by a separate thread and skips frames while the terminal is busy. Default: no preview
+    #[argh(option)]
+    pub terminal_preview_ms: Option<u64>,
+    /// explicitly drive the output enable to off while the row address changes and the row is strobed, instead
+    /// of relying on the previous pulse having ended. Removes ghosting on fast panels at the cost of a little
+    /// brightness. Default: false
+    #[argh(option, default = "false")]
+    pub strict_blanking: bool,
+    /// the number of frames the frame rate is averaged over. Default: 60
+    #[argh(option, default = "FRAMERATE_WINDOW")]
+    pub framerate_window: usize,
+    /// the on-time of the lowest significant bit in nanoseconds while the quality mode is enabled with
+    /// `RGBMatrix::set_quality_mode`, e.g. for more shadow detail in a paused image at a lower refresh rate.
+    /// Default: twice --pwm-lsb-nanoseconds
+    #[argh(option)]
+    pub quality_pwm_lsb_nanoseconds: Option<u32>,
+    /// the panels clock in data on the falling edge of a clock that idles high, instead of the usual rising
+    /// edge. Try this if the output is shifted by one pixel. Default: false
+    #[argh(option, default = "false")]
+    pub clock_falling_edge: bool,
+    /// the brightness in percent of each panel along the chain, e.g. "94,97,100" to dim the first panels of a
+    /// long chain to match the last ones, which are darker due to voltage drop. Default: full brightness
+    #[argh(option)]
+    pub chain_gains: Option<ChainGains>,
+    /// lower the PWM bits one at a time, down to this number, while the update thread can't reach the refresh
+    /// rate. The PWM bits are not raised again. RGBMatrix::adapted_pwm_bits reports the PWM bits in use.
+    /// Default: not adapted
+    #[argh(option)]
+    pub min_pwm_bits: Option<usize>,
+    /// map the GPIO registers through /dev/gpiomem instead of /dev/mem. Creating the matrix fails if
+    /// /dev/gpiomem can't be mapped. The PWM, clock and timer registers have no such device and still need
+    /// /dev/mem, so this doesn't avoid running as root. Default: false
+    #[argh(switch)]
+    pub gpiomem: bool,
+    /// the GPIO bits that are written while clocking in the color data, e.g. "0x3fc0000", overriding the mask of
+    /// the color and clock pins of the hardware mapping, for unusual wiring. Default: derived from the hardware
+    /// mapping
+    #[argh(option, from_str_fn(parse_gpio_mask))]
+    pub color_clock_mask: Option<u32>,
+    /// show each canvas pixel as a block of this many matrix pixels along each axis, e.g. 2 to draw a
+    /// half-resolution canvas that is pixel-doubled to fill the panels. This cuts the drawing work on slow Pis
+    /// with large displays. Matrix pixels beyond the last whole block stay dark. Default: 1
+    #[argh(option, default = "1")]
+    pub pixel_scale: usize,
+    /// only print a summary of the configuration and exit, without accessing the hardware. Meant for
+    /// applications, like the examples, to check the options on any machine. Creating a matrix fails instead of
+    /// accessing the hardware. Default: false
+    #[argh(switch)]
+    pub dry_run: bool,
+    /// skip the check that the peripherals can be accessed through /dev/mem before the update thread starts,
+    /// e.g. where the check fails although the registers can be mapped. If mapping the registers fails anyway,
+    /// the update thread panics and creating the matrix fails with a timeout. Default: false
+    #[argh(switch)]
+    pub skip_memory_check: bool,
+}
+
+/// Parses a GPIO bit mask, either in decimal or in hexadecimal with a "0x" prefix.
+fn parse_gpio_mask(value: &str) -> Result<u32, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid GPIO mask: {value}"))
+}
+
+/// Parses the dither bits, rejecting values outside of [`RGBMatrixConfig::SUPPORTED_DITHER_BITS`].
+fn parse_dither_bits(value: &str) -> Result<usize, String> {
+    let supported = RGBMatrixConfig::SUPPORTED_DITHER_BITS;
+    value
+        .parse()
+        .ok()
+        .filter(|bits| supported.contains(bits))
+        .ok_or_else(|| {
+            format!(
+                "Unsupported dither bits '{value}', expected {} to {}",
+                supported.start(),
+                supported.end()
+            )
+        })
 }
 
 impl RGBMatrixConfig {
+    /// The values of [`RGBMatrixConfig::dither_bits`] that a matrix can be created with.
+    pub const SUPPORTED_DITHER_BITS: RangeInclusive<usize> = 0..=2;
+
+    /// The scan rate of the small-pitch indoor panels, e.g. P2.5 and P3, that
+    /// [`RGBMatrixConfig::indoor_1_16_checkered`] configures.
+    const INDOOR_CHECKERED_SCAN: usize = 16;
+
+    /// A configuration for the common small-pitch indoor panels, e.g. P2.5 and P3, that scan 1:16 with a
+    /// checkered multiplexing and address their rows directly. All other settings are the defaults.
+    ///
+    /// Returns [`MatrixCreationError::InvalidScan`] if a panel with `rows` rows can't be scanned like this, i.e.
+    /// unless it has 64 rows, and [`MatrixCreationError::InvalidGeometry`] for zero or odd columns, a chain
+    /// length of zero or too many pixels.
+    pub fn indoor_1_16_checkered(
+        rows: usize,
+        cols: usize,
+        chain_length: usize,
+    ) -> Result<Self, MatrixCreationError> {
+        let config = Self {
+            rows,
+            cols,
+            chain_length,
+            multiplexing: Some(MultiplexMapperType::Checkered),
+            row_setter: RowAddressSetterType::Direct,
+            scan: Some(Self::INDOOR_CHECKERED_SCAN),
+            ..Default::default()
+        };
+        // The checkered mapper swaps the halves of each panel row.
+        if config.checked_pixels().is_none() || !cols.is_multiple_of(2) {
+            return Err(MatrixCreationError::InvalidGeometry);
+        }
+        if !config.valid_sub_panels() {
+            return Err(MatrixCreationError::InvalidScan(
+                Self::INDOOR_CHECKERED_SCAN,
+            ));
+        }
+        config.scan_multiplexing(Self::INDOOR_CHECKERED_SCAN)?;
+        Ok(config)
+    }
+
+    /// A human-readable description of the geometry and the mapping that a matrix created with this
+    /// configuration would use, with the panel profile applied. This doesn't access the hardware, so it can be
+    /// used to check the options on any machine.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut config = self.clone();
+        if let Some(profile) = config.panel_profile {
+            profile.apply(&mut config);
+        }
+
+        let mut lines = vec![format!(
+            "Panels: {} rows x {} columns, {} chained, {} parallel",
+            config.rows, config.cols, config.chain_length, config.parallel
+        )];
+        if config.checked_pixels().is_none() || !config.valid_sub_panels() {
+            lines.push("Canvas: invalid geometry".to_string());
+            return lines.join("\n");
+        }
+
+        let wired_chains = config.hardware_mapping.max_parallel_chains();
+        if config.used_chains() > wired_chains {
+            lines.push(format!(
+                "Parallel chains: the hardware mapping wires only {} chains",
+                wired_chains / config.chains_per_panel()
+            ));
+        }
+
+        let multiplexing = match config.scan.map(|scan| config.scan_multiplexing(scan)) {
+            Some(Ok(multiplexing)) => multiplexing,
+            Some(Err(error)) => {
+                lines.push(format!("Scan: {error}"));
+                config.multiplexing
+            }
+            None => config.multiplexing,
+        };
+        if let Some(multiplexing) = multiplexing
+            .filter(|multiplexing| !multiplexing.supports_panel(config.rows, config.cols))
+        {
+            lines.push(format!(
+                "Multiplexing: {multiplexing:?} does not support {} x {} panels",
+                config.rows, config.cols
+            ));
+            return lines.join("\n");
+        }
+        let scanned_rows = config.double_rows()
+            / multiplexing.map_or(1, |multiplexing| {
+                multiplexing.create().panel_stretch_factor()
+            });
+        lines.push(format!(
+            "Multiplexing: {}, 1:{scanned_rows} scan with {} sub-panels",
+            multiplexing.map_or("none".to_string(), |multiplexing| format!(
+                "{multiplexing:?}"
+            )),
+            config.sub_panels
+        ));
+        lines.push(format!(
+            "Pixel mappers: {}",
+            if config.pixelmapper.is_empty() {
+                "none".to_string()
+            } else {
+                format!("{:?}", config.pixelmapper)
+            }
+        ));
+
+        config.multiplexing = multiplexing;
+        let shared_mapper = match RGBMatrix::create_shared_mapper(&mut config) {
+            Ok(shared_mapper) => shared_mapper,
+            Err(error) => {
+                lines.push(format!("Canvas: {error}"));
+                return lines.join("\n");
+            }
+        };
+        lines.push(format!(
+            "Canvas: {} x {} pixels{}",
+            shared_mapper.width(),
+            shared_mapper.height(),
+            if shared_mapper.scale() > 1 {
+                format!(
+                    ", each shown on {0} x {0} matrix pixels",
+                    shared_mapper.scale()
+                )
+            } else {
+                String::new()
+            }
+        ));
+        lines.push(format!(
+            "Row address setter: {:?}, LED sequence: {:?}",
+            config.row_setter, config.led_sequence
+        ));
+        lines.push(format!(
+            "PWM: {} bits{}, {} ns LSB, {} Hz refresh rate, at most about {:.0} Hz reachable",
+            config.pwm_bits,
+            match config.bit_plane_shift() {
+                Some(0) => String::new(),
+                Some(shift) => format!(" with the on-times of {shift} bit planes lower"),
+                None => " with an invalid first bit plane".to_string(),
+            },
+            config.pwm_lsb_nanoseconds,
+            config.refresh_rate,
+            self.theoretical_max_refresh()
+        ));
+        lines.join("\n")
+    }
+
+    /// The lowest bit plane shown in each frame of the dithering sequence, if the dither bits are supported.
+    pub(crate) const fn dither_start_bits(&self) -> Option<[usize; 4]> {
+        match self.dither_bits {
+            0 => Some([0, 0, 0, 0]),
+            1 => Some([0, 1, 0, 1]),
+            2 => Some([0, 1, 2, 2]),
+            _ => None,
+        }
+    }
+
+    /// The on-time of each bit plane of the canvas for an on-time of `lsb_nanoseconds` of the lowest plane.
+    /// The on-times double from plane to plane above the dither bits. With a
+    /// [`RGBMatrixConfig::first_bit_plane`] below the default, the planes get the on-times of lower planes.
+    pub(crate) fn bitplane_timings_ns(&self, lsb_nanoseconds: u32) -> [u32; K_BIT_PLANES] {
+        let mut timing_ns = lsb_nanoseconds;
+        let timings: [u32; K_BIT_PLANES] = std::array::from_fn(|plane| {
+            let plane_timing_ns = timing_ns;
+            if plane >= self.dither_bits {
+                timing_ns = timing_ns.saturating_mul(2);
+            }
+            plane_timing_ns
+        });
+        let shift = self.bit_plane_shift().unwrap_or(0);
+        std::array::from_fn(|plane| timings[plane.saturating_sub(shift)])
+    }
+
+    /// The number of bit planes the on-times are shifted down by [`RGBMatrixConfig::first_bit_plane`], if it
+    /// leaves room for the PWM bits.
+    pub(crate) fn bit_plane_shift(&self) -> Option<usize> {
+        let default_first = K_BIT_PLANES - self.pwm_bits.clamp(1, K_BIT_PLANES);
+        default_first.checked_sub(self.first_bit_plane.unwrap_or(default_first))
+    }
+
+    /// Estimate the highest refresh rate in Hz that the update thread can reach with this configuration, e.g. to
+    /// choose [`RGBMatrixConfig::refresh_rate`] before deploying. Each bit plane of each scanned row takes the
+    /// longer of its on-time and the time to clock in the next bit plane, which is estimated from the GPIO
+    /// slowdown. Without a slowdown or [`RGBMatrixConfig::pi_chip`], no slowdown is assumed, so the estimate is
+    /// an upper bound. Dithering is averaged over its frames.
+    #[must_use]
+    pub fn theoretical_max_refresh(&self) -> f64 {
+        let mut config = self.clone();
+        if let Some(profile) = config.panel_profile {
+            profile.apply(&mut config);
+        }
+        if let Some(Ok(multiplexing)) = config.scan.map(|scan| config.scan_multiplexing(scan)) {
+            config.multiplexing = multiplexing;
+        }
+        if let Some(multiplexing) = config.multiplexing {
+            multiplexing
+                .create()
+                .edit_rows_cols(&mut config.rows, &mut config.cols);
+        }
+
+        let slowdown = config
+            .slowdown
+            .or(config.pi_chip.map(PiChip::gpio_slowdown))
+            .unwrap_or(0);
+        // Each column clears and sets the color bits and sets the clock, repeated for the slowdown.
+        let writes = 3 * (slowdown as usize + 1) * config.cols * config.chain_length;
+        let clock_in_ns = writes as f64 * GPIO_WRITE_NS;
+
+        let plane_times_ns = config
+            .bitplane_timings_ns(config.pwm_lsb_nanoseconds)
+            .map(|on_time_ns| f64::from(on_time_ns).max(clock_in_ns));
+
+        let lowest_plane = K_BIT_PLANES - config.pwm_bits.clamp(1, K_BIT_PLANES);
+        let dither_start_bits = config.dither_start_bits().unwrap_or([0; 4]);
+        let row_time_ns = dither_start_bits
+            .iter()
+            .map(|&dither_start_bit| {
+                plane_times_ns[lowest_plane.max(dither_start_bit)..]
+                    .iter()
+                    .sum::<f64>()
+            })
+            .sum::<f64>()
+            / dither_start_bits.len() as f64;
+        1e9 / (row_time_ns * config.double_rows() as f64)
+    }
+
     pub(crate) const fn double_rows(&self) -> usize {
-        self.rows / SUB_PANELS
+        self.rows / self.sub_panels
+    }
+
+    /// The number of chains whose color pins are needed to drive a single panel.
+    pub(crate) const fn chains_per_panel(&self) -> usize {
+        self.sub_panels.div_ceil(SUB_PANELS_PER_CHAIN)
+    }
+
+    /// The on-time of the lowest significant bit in the quality mode.
+    pub(crate) fn quality_pwm_lsb_nanoseconds(&self) -> u32 {
+        self.quality_pwm_lsb_nanoseconds
+            .unwrap_or(2 * self.pwm_lsb_nanoseconds)
+    }
+
+    /// The order in which the rows are refreshed, taking `interlaced` into account.
+    pub(crate) fn row_order(&self) -> RowOrder {
+        match &self.row_order {
+            Some(row_order) => row_order.clone(),
+            None if self.interlaced => RowOrder::Interlaced,
+            None => RowOrder::Sequential,
+        }
+    }
+
+    /// The number of chains whose color pins are in use.
+    pub(crate) const fn used_chains(&self) -> usize {
+        self.parallel * self.chains_per_panel()
+    }
+
+    /// Check the declared scan rate against the rows, the multiplexing and the row setter and return the
+    /// multiplexing to use. A multiplexing mapper has to fold the rows of the panel onto the scanned rows, which
+    /// the row setter has to be able to select.
+    pub(crate) fn scan_multiplexing(
+        &self,
+        scan: usize,
+    ) -> Result<Option<MultiplexMapperType>, MatrixCreationError> {
+        let double_rows = self.double_rows();
+        if scan == 0 || !double_rows.is_multiple_of(scan) {
+            return Err(MatrixCreationError::InvalidScan(scan));
+        }
+        if self
+            .row_setter
+            .max_scanned_rows()
+            .is_some_and(|max_rows| scan > max_rows)
+        {
+            return Err(MatrixCreationError::UnsupportedScan(scan, self.row_setter));
+        }
+        let stretch_factor = double_rows / scan;
+        match self.multiplexing {
+            None if stretch_factor == 1 => Ok(None),
+            None if stretch_factor == 2 => Ok(Some(MultiplexMapperType::Stripe)),
+            Some(multiplexing)
+                if multiplexing.create().panel_stretch_factor() == stretch_factor =>
+            {
+                Ok(Some(multiplexing))
+            }
+            _ => Err(MatrixCreationError::InvalidScan(scan)),
+        }
+    }
+
+    /// The number of pixels of the matrix, if the geometry is valid: no dimension is zero and the pixels don't
+    /// exceed [`MAX_PIXELS`].
+    pub(crate) fn checked_pixels(&self) -> Option<usize> {
+        let width = self.cols.checked_mul(self.chain_length)?;
+        let height = self.rows.checked_mul(self.parallel)?;
+        width
+            .checked_mul(height)
+            .filter(|pixels| (1..=MAX_PIXELS).contains(pixels))
+    }
+
+    pub(crate) const fn valid_sub_panels(&self) -> bool {
+        self.sub_panels > 0
+            && self.rows.is_multiple_of(self.sub_panels)
+            && self.double_rows() <= MAX_ADDRESSABLE_ROWS
     }
 }
 
@@ -109,18 +613,237 @@ impl Default for RGBMatrixConfig {
             refresh_rate: 120,
             pi_chip: None,
             pwm_bits: 11,
+            first_bit_plane: None,
             pwm_lsb_nanoseconds: 130,
             slowdown: None,
             interlaced: false,
+            row_order: None,
             dither_bits: 0,
             chain_length: 1,
             parallel: 1,
             panel_type: None,
+            panel_types: None,
             multiplexing: None,
             pixelmapper: vec![],
             row_setter: RowAddressSetterType::Direct,
             led_sequence: LedSequence::Rgb,
+            led_sequence_lower: None,
             led_brightness: 100,
+            frame_sink: None,
+            cpu_core: None,
+            quiet: false,
+            blank_on_drop: true,
+            sub_panels: SUB_PANELS,
+            min_sys_sleep_time_us: MIN_SYS_SLEEP_TIME_US,
+            gpio_settle_us: GPIO_SETTLE_US,
+            panel_profile: None,
+            input_pull: None,
+            input_queue_size: INPUT_QUEUE_SIZE,
+            oe_active_high: false,
+            e_pin: None,
+            scan: None,
+            black_level_lift: 0,
+            preview_interval: None,
+            terminal_preview_ms: None,
+            strict_blanking: false,
+            framerate_window: FRAMERATE_WINDOW,
+            quality_pwm_lsb_nanoseconds: None,
+            clock_falling_edge: false,
+            chain_gains: None,
+            min_pwm_bits: None,
+            gpiomem: false,
+            color_clock_mask: None,
+            pixel_scale: 1,
+            dry_run: false,
+            skip_memory_check: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dither_bits, MAX_PIXELS};
+    use crate::{
+        rgb_matrix::MatrixCreationError, MultiplexMapperType, RGBMatrixConfig, RowAddressSetterType,
+    };
+
+    #[test]
+    fn test_scan_multiplexing() {
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 32,
+            ..Default::default()
+        };
+        assert_eq!(config.scan_multiplexing(16).unwrap(), None);
+        assert_eq!(
+            config.scan_multiplexing(8).unwrap(),
+            Some(MultiplexMapperType::Stripe)
+        );
+        assert!(config.scan_multiplexing(4).is_err());
+        assert!(config.scan_multiplexing(5).is_err());
+
+        let config = RGBMatrixConfig {
+            multiplexing: Some(MultiplexMapperType::Kaler2Scan),
+            ..config
+        };
+        assert_eq!(
+            config.scan_multiplexing(4).unwrap(),
+            Some(MultiplexMapperType::Kaler2Scan)
+        );
+        assert!(config.scan_multiplexing(8).is_err());
+
+        let config = RGBMatrixConfig {
+            row_setter: RowAddressSetterType::DirectABCDLine,
+            ..config
+        };
+        assert!(config.scan_multiplexing(4).is_ok());
+        let config = RGBMatrixConfig {
+            multiplexing: None,
+            ..config
+        };
+        assert!(matches!(
+            config.scan_multiplexing(16),
+            Err(MatrixCreationError::UnsupportedScan(16, _))
+        ));
+    }
+
+    #[test]
+    fn test_checked_pixels() {
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 64,
+            chain_length: 2,
+            parallel: 3,
+            ..Default::default()
+        };
+        assert_eq!(config.checked_pixels(), Some(32 * 64 * 2 * 3));
+        let config = RGBMatrixConfig {
+            chain_length: 0,
+            ..config
+        };
+        assert_eq!(config.checked_pixels(), None);
+        let config = RGBMatrixConfig {
+            chain_length: usize::MAX / 2,
+            ..config
+        };
+        assert_eq!(config.checked_pixels(), None);
+        let config = RGBMatrixConfig {
+            chain_length: MAX_PIXELS / 32,
+            ..config
+        };
+        assert_eq!(config.checked_pixels(), None);
+    }
+
+    #[test]
+    fn test_parse_dither_bits() {
+        for bits in RGBMatrixConfig::SUPPORTED_DITHER_BITS {
+            assert_eq!(parse_dither_bits(&bits.to_string()), Ok(bits));
+        }
+        let error = parse_dither_bits("3").unwrap_err();
+        assert!(error.contains("expected 0 to 2"));
+        assert!(parse_dither_bits("-1").is_err());
+    }
+
+    #[test]
+    fn test_theoretical_max_refresh() {
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 64,
+            pwm_bits: 11,
+            pwm_lsb_nanoseconds: 130,
+            slowdown: Some(0),
+            ..Default::default()
+        };
+        // 16 rows of 11 bit planes, each taking at least the 1920 ns to clock in 64 columns.
+        let on_times: f64 = (0..11)
+            .map(|plane| f64::from(130 << plane).max(1920.0))
+            .sum();
+        let expected = 1e9 / (16.0 * on_times);
+        assert!((config.theoretical_max_refresh() - expected).abs() < 1e-6);
+
+        // Fewer PWM bits and dithering are faster, longer chains and slowdowns are slower.
+        let rate = config.theoretical_max_refresh();
+        for faster in [
+            RGBMatrixConfig {
+                pwm_bits: 8,
+                ..config.clone()
+            },
+            RGBMatrixConfig {
+                dither_bits: 2,
+                ..config.clone()
+            },
+        ] {
+            assert!(faster.theoretical_max_refresh() > rate);
+        }
+        // So are the shorter on-times of lower bit planes.
+        let top_planes = RGBMatrixConfig {
+            pwm_bits: 8,
+            ..config.clone()
+        };
+        let lower_planes = RGBMatrixConfig {
+            first_bit_plane: Some(1),
+            ..top_planes.clone()
+        };
+        assert_eq!(lower_planes.bit_plane_shift(), Some(2));
+        assert!(lower_planes.theoretical_max_refresh() > top_planes.theoretical_max_refresh());
+        assert_eq!(
+            RGBMatrixConfig {
+                first_bit_plane: Some(4),
+                ..top_planes
+            }
+            .bit_plane_shift(),
+            None
+        );
+        for slower in [
+            RGBMatrixConfig {
+                chain_length: 4,
+                ..config.clone()
+            },
+            RGBMatrixConfig {
+                slowdown: Some(3),
+                ..config.clone()
+            },
+        ] {
+            assert!(slower.theoretical_max_refresh() < rate);
         }
     }
+
+    #[test]
+    fn test_summary() {
+        let config = RGBMatrixConfig {
+            rows: 32,
+            cols: 64,
+            chain_length: 2,
+            scan: Some(8),
+            ..Default::default()
+        };
+        let summary = config.summary();
+        assert!(summary.contains("Multiplexing: Stripe, 1:8 scan"));
+        assert!(summary.contains("Canvas: 128 x 32 pixels"));
+        let config = RGBMatrixConfig {
+            chain_length: 0,
+            ..config
+        };
+        assert!(config.summary().contains("invalid geometry"));
+    }
+
+    #[test]
+    fn test_indoor_1_16_checkered() {
+        let config = RGBMatrixConfig::indoor_1_16_checkered(64, 64, 3).unwrap();
+        assert_eq!(config.multiplexing, Some(MultiplexMapperType::Checkered));
+        assert_eq!(config.scan, Some(16));
+        assert_eq!(config.chain_length, 3);
+        assert!(matches!(
+            RGBMatrixConfig::indoor_1_16_checkered(32, 64, 1),
+            Err(MatrixCreationError::InvalidScan(16))
+        ));
+        assert!(matches!(
+            RGBMatrixConfig::indoor_1_16_checkered(64, 63, 1),
+            Err(MatrixCreationError::InvalidGeometry)
+        ));
+        assert!(matches!(
+            RGBMatrixConfig::indoor_1_16_checkered(64, 64, 0),
+            Err(MatrixCreationError::InvalidGeometry)
+        ));
+    }
 }
@@ -0,0 +1,211 @@
+use std::{collections::VecDeque, io::Write};
+
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::Point,
+    text::{Baseline, Text},
+    Drawable,
+};
+
+use crate::{Canvas, RGBMatrix};
+
+/// A text console on the matrix, e.g. to show debug output of a headless Pi with `writeln!`.
+///
+/// The written bytes are shown as lines of text in a monospaced font. Lines that are too long wrap, and the
+/// oldest lines scroll out at the top once the canvas is full. Like a [`std::io::LineWriter`], the console is
+/// redrawn at the end of each line and on [`Write::flush`].
+pub struct ConsoleCanvas {
+    matrix: RGBMatrix,
+    text: ConsoleText,
+    font: &'static MonoFont<'static>,
+    color: Rgb888,
+}
+
+/// The lines of a console, independent of the matrix.
+struct ConsoleText {
+    columns: usize,
+    rows: usize,
+    /// The lines from top to bottom. The last one is the line that is being written.
+    lines: VecDeque<String>,
+    /// The start of a UTF-8 character that was split between writes.
+    partial: Vec<u8>,
+}
+
+impl ConsoleText {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns: columns.max(1),
+            rows: rows.max(1),
+            lines: VecDeque::from([String::new()]),
+            partial: Vec::new(),
+        }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(text) => text.len(),
+            // Keep an incomplete character at the end for the next write, skip invalid bytes.
+            Err(error) => match error.error_len() {
+                Some(_) => self.partial.len(),
+                None => error.valid_up_to(),
+            },
+        };
+        let partial = self.partial.split_off(valid);
+        let text = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial = partial;
+        text.chars().for_each(|c| self.push_char(c));
+    }
+
+    fn push_char(&mut self, c: char) {
+        match c {
+            '\n' => self.new_line(),
+            '\r' => {}
+            '\t' => self.push_char(' '),
+            c => {
+                if self.current_line().chars().count() == self.columns {
+                    self.new_line();
+                }
+                self.lines.back_mut().unwrap().push(c);
+            }
+        }
+    }
+
+    fn current_line(&self) -> &str {
+        self.lines.back().unwrap()
+    }
+
+    fn new_line(&mut self) {
+        self.lines.push_back(String::new());
+        while self.lines.len() > self.rows {
+            self.lines.pop_front();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.lines = VecDeque::from([String::new()]);
+        self.partial.clear();
+    }
+
+    fn draw(&self, canvas: &mut Canvas, font: &'static MonoFont<'static>, color: Rgb888) {
+        canvas.clear();
+        let style = MonoTextStyle::new(font, color);
+        let line_height = font.character_size.height as i32;
+        for (row, line) in self.lines.iter().enumerate() {
+            let origin = Point::new(0, row as i32 * line_height);
+            Text::with_baseline(line, origin, style, Baseline::Top)
+                .draw(canvas)
+                .unwrap();
+        }
+    }
+}
+
+impl ConsoleCanvas {
+    /// Show a console on the whole matrix, with as many lines and columns of text in `font` as fit on the
+    /// canvas. The console uses [`RGBMatrix::with_canvas`] to update the display, so don't update the matrix
+    /// in other ways while using it.
+    #[must_use]
+    pub fn new(
+        matrix: RGBMatrix,
+        canvas: &Canvas,
+        font: &'static MonoFont<'static>,
+        color: Rgb888,
+    ) -> Self {
+        let character_width = (font.character_size.width + font.character_spacing) as usize;
+        let columns = canvas.width() / character_width.max(1);
+        let rows = canvas.height() / (font.character_size.height as usize).max(1);
+        Self {
+            matrix,
+            text: ConsoleText::new(columns, rows),
+            font,
+            color,
+        }
+    }
+
+    /// Change the color of the text, which applies on the next redraw.
+    pub fn set_color(&mut self, color: Rgb888) {
+        self.color = color;
+    }
+
+    /// Remove all text and blank the display.
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.redraw();
+    }
+
+    /// The matrix the console is shown on, e.g. to change the brightness.
+    pub fn matrix(&mut self) -> &mut RGBMatrix {
+        &mut self.matrix
+    }
+
+    /// Stop using the console and return the matrix.
+    #[must_use]
+    pub fn into_matrix(self) -> RGBMatrix {
+        self.matrix
+    }
+
+    fn redraw(&mut self) {
+        let Self {
+            matrix,
+            text,
+            font,
+            color,
+        } = self;
+        matrix.with_canvas(|canvas| text.draw(canvas, font, *color));
+    }
+}
+
+impl Write for ConsoleCanvas {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.text.push_bytes(buf);
+        if buf.contains(&b'\n') {
+            self.redraw();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.redraw();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{mono_font::ascii::FONT_6X10, pixelcolor::Rgb888, prelude::RgbColor};
+
+    use super::ConsoleText;
+    use crate::{Canvas, RGBMatrixConfig};
+
+    #[test]
+    fn test_console_text() {
+        let mut text = ConsoleText::new(5, 3);
+        text.push_bytes(b"ab\r\ncdefgh");
+        assert_eq!(text.lines, ["ab", "cdefg", "h"]);
+        // Full lines scroll out at the top.
+        text.push_bytes(b"\nij");
+        assert_eq!(text.lines, ["cdefg", "h", "ij"]);
+
+        // Characters split between writes are kept until they are complete.
+        let bytes = "\u{e4}".as_bytes();
+        text.push_bytes(&bytes[..1]);
+        assert_eq!(text.lines[2], "ij");
+        text.push_bytes(&bytes[1..]);
+        assert_eq!(text.lines[2], "ij\u{e4}");
+
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        text.draw(&mut canvas, &FONT_6X10, Rgb888::WHITE);
+        let lit_rows: Vec<usize> = (0..canvas.height())
+            .filter(|&y| (0..canvas.width()).any(|x| canvas.get_color(x, y) != [0, 0, 0]))
+            .collect();
+        // The first two lines are drawn, the third one is below the canvas.
+        assert!(lit_rows.iter().any(|&y| y < 10));
+        assert!(lit_rows.iter().any(|&y| y >= 10));
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    thread::spawn,
+};
+
+use crate::{
+    frame_snapshot::{frame_snapshots, SnapshotSender},
+    Canvas,
+};
+
+/// Writes the displayed frames as raw RGB bytes to a file or named pipe. The decoding and writing happen on a
+/// separate thread so that neither a slow reader nor a large display stalls the display update. Frames that
+/// arrive while the previous one is still being written are dropped.
+pub(crate) struct FrameSink {
+    snapshot_sender: SnapshotSender,
+}
+
+impl FrameSink {
+    pub(crate) fn new(path: PathBuf, canvas: &Canvas) -> Self {
+        let (snapshot_sender, snapshot_receiver) = frame_snapshots(canvas);
+        spawn(move || {
+            let mut file: Option<File> = None;
+            while let Some(frame) = snapshot_receiver.recv() {
+                // Opening a named pipe blocks until there is a reader, which is fine on this thread.
+                if file.is_none() {
+                    file = OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&path)
+                        .ok();
+                }
+                let Some(writer) = file.as_mut() else {
+                    continue;
+                };
+                if writer.write_all(&frame).is_err() {
+                    // The reader went away. Try to reopen with the next frame.
+                    file = None;
+                }
+            }
+        });
+        Self { snapshot_sender }
+    }
+
+    /// Hand the frame of the canvas to the writer thread. Drops the frame if the writer is still busy.
+    pub(crate) fn push(&self, canvas: &Canvas) {
+        self.snapshot_sender.push(canvas);
+    }
+}
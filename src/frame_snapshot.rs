@@ -0,0 +1,102 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::Canvas;
+
+/// Create a channel for snapshots of the frames displayed by the update thread. The update thread only copies
+/// the bit planes into a spare canvas, so it neither decodes the frames nor allocates. The receiving side
+/// decodes the snapshots into RGB bytes and hands the spare canvas back. Frames that are pushed while the
+/// receiving side still holds the spare canvas are dropped.
+pub(crate) fn frame_snapshots(canvas: &Canvas) -> (SnapshotSender, SnapshotReceiver) {
+    let (snapshot_sender, snapshot_receiver) = channel();
+    let (spare_sender, spare_receiver) = channel();
+    spare_sender.send(Box::new(canvas.clone())).ok();
+    (
+        SnapshotSender {
+            snapshot_sender,
+            spare_receiver,
+        },
+        SnapshotReceiver {
+            snapshot_receiver,
+            spare_sender,
+        },
+    )
+}
+
+pub(crate) struct SnapshotSender {
+    snapshot_sender: Sender<Box<Canvas>>,
+    spare_receiver: Receiver<Box<Canvas>>,
+}
+
+impl SnapshotSender {
+    /// Copy the frame of the canvas into the spare canvas and send it. Drops the frame if the spare canvas
+    /// wasn't handed back yet.
+    pub(crate) fn push(&self, canvas: &Canvas) {
+        if let Ok(mut spare) = self.spare_receiver.try_recv() {
+            spare.copy_frame_from(canvas);
+            self.snapshot_sender.send(spare).ok();
+        }
+    }
+}
+
+pub(crate) struct SnapshotReceiver {
+    snapshot_receiver: Receiver<Box<Canvas>>,
+    spare_sender: Sender<Box<Canvas>>,
+}
+
+impl SnapshotReceiver {
+    /// Wait for the next snapshot and decode it into row-major RGB bytes. Returns `None` once the sender is
+    /// gone.
+    pub(crate) fn recv(&self) -> Option<Vec<u8>> {
+        self.snapshot_receiver
+            .recv()
+            .ok()
+            .map(|snapshot| self.decode(snapshot))
+    }
+
+    /// Like [`SnapshotReceiver::recv`], but returns `None` right away if there is no snapshot.
+    pub(crate) fn try_recv(&self) -> Option<Vec<u8>> {
+        self.snapshot_receiver
+            .try_recv()
+            .ok()
+            .map(|snapshot| self.decode(snapshot))
+    }
+
+    fn decode(&self, snapshot: Box<Canvas>) -> Vec<u8> {
+        let bytes = snapshot.to_rgb_bytes();
+        self.spare_sender.send(snapshot).ok();
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::frame_snapshots;
+    use crate::{Canvas, LedSequence, RGBMatrixConfig};
+
+    #[test]
+    fn test_frame_snapshots() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut canvas = Canvas::for_config(&config);
+        let (sender, receiver) = frame_snapshots(&canvas);
+        assert_eq!(receiver.try_recv(), None);
+
+        canvas.set_pixel(3, 4, 255, 0, 0);
+        sender.push(&canvas);
+        // The frame is dropped while the snapshot isn't received.
+        canvas.set_pixel(3, 4, 0, 255, 0);
+        sender.push(&canvas);
+        let bytes = receiver.try_recv().unwrap();
+        assert_eq!(&bytes[3 * (4 * 32 + 3)..][..3], [255, 0, 0]);
+        assert_eq!(receiver.try_recv(), None);
+
+        // The LED sequence is taken over as well.
+        canvas.set_led_sequence(LedSequence::Bgr);
+        canvas.set_pixel(3, 4, 0, 0, 255);
+        sender.push(&canvas);
+        assert_eq!(receiver.recv().unwrap(), canvas.to_rgb_bytes());
+    }
+}
@@ -1,14 +1,16 @@
 use std::{
     error::Error,
     fmt::{Display, Formatter},
+    sync::atomic::{AtomicBool, Ordering},
+    thread::sleep,
+    time::Duration,
 };
 
 use crate::{
     chip::PiChip,
-    config::K_BIT_PLANES,
     gpio_bits,
     pin_pulser::PinPulser,
-    registers::{ClkRegisters, GPIOFunction, GPIORegisters, PWMRegisters, TimeRegisters},
+    registers::{ClkRegisters, GPIOFunction, GPIORegisters, PWMRegisters, PullMode, TimeRegisters},
     row_address_setter::RowAddressSetter,
     utils::linux_has_module_loaded,
     RGBMatrixConfig,
@@ -18,6 +20,9 @@ use crate::{
 pub enum GpioInitializationError {
     OneWireProtocolEnabled,
     SoundModuleLoaded,
+    ConflictingModuleLoaded { module: &'static str, pins: Vec<u8> },
+    AlreadyInUse,
+    GpioMemUnavailable,
 }
 
 impl Error for GpioInitializationError {}
@@ -37,11 +42,62 @@ impl Display for GpioInitializationError {
                 `/etc/modprobe.d/alsa-blacklist.conf`\n\
                 Finally, reboot the system and try again.",
             ),
+            GpioInitializationError::ConflictingModuleLoaded { module, pins } => {
+                let pins = pins
+                    .iter()
+                    .map(|pin| pin.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "The kernel module `{module}` is loaded and claims GPIO {pins}, which the hardware mapping \
+                    uses.\nThis will mess with the display. Disable the interface in raspi-config \
+                    (Interface Options) or choose a hardware mapping without these pins."
+                )
+            }
+            GpioInitializationError::AlreadyInUse => f.write_str(
+                "The GPIO pins are already in use by another matrix in this process.\n\
+                Only one matrix can be driven at a time. Drop the other one first.",
+            ),
+            GpioInitializationError::GpioMemUnavailable => f.write_str(
+                "'/dev/gpiomem' could not be mapped. Add the user to the `gpio` group, or don't use --gpiomem \
+                to map the GPIO registers through '/dev/mem'.",
+            ),
         }
     }
 }
 
+/// Kernel modules that claim GPIO pins the hardware mappings may use, with the claimed pins.
+const CONFLICTING_MODULES: [(&str, &[u8]); 3] = [
+    ("spi_bcm2835", &[7, 8, 9, 10, 11]),
+    ("i2c_bcm2835", &[2, 3]),
+    ("i2c_bcm2708", &[2, 3]),
+];
+
+/// Whether a [`Gpio`] instance currently owns the pins. The pin setup, e.g. the reservation of GPIO 4 for the
+/// Adafruit HAT PWM modification, assumes sole ownership.
+static GPIO_IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// Process-wide claim of the GPIO pins. Released when dropped.
+struct GpioClaim;
+
+impl GpioClaim {
+    fn acquire() -> Option<Self> {
+        GPIO_IN_USE
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for GpioClaim {
+    fn drop(&mut self) {
+        GPIO_IN_USE.store(false, Ordering::Release);
+    }
+}
+
 pub(crate) struct Gpio {
+    _claim: GpioClaim,
     gpio_registers: GPIORegisters,
     time_registers: TimeRegisters,
     pwm_registers: PWMRegisters,
@@ -50,6 +106,8 @@ pub(crate) struct Gpio {
     output_bits: u32,
     reserved_bits: u32,
     gpio_slowdown: u32,
+    oe_active_high: bool,
+    strict_blanking: bool,
 }
 
 impl Gpio {
@@ -63,9 +121,12 @@ impl Gpio {
             return Err(GpioInitializationError::SoundModuleLoaded);
         }
 
-        let mut gpio_registers = GPIORegisters::new(chip);
-        let time_registers = TimeRegisters::new(chip);
-        let mut pwm_registers = PWMRegisters::new(chip);
+        let claim = GpioClaim::acquire().ok_or(GpioInitializationError::AlreadyInUse)?;
+
+        let mut gpio_registers = GPIORegisters::new(chip, config.gpiomem)
+            .ok_or(GpioInitializationError::GpioMemUnavailable)?;
+        let time_registers = TimeRegisters::new(chip, config.min_sys_sleep_time_us);
+        let mut pwm_registers = PWMRegisters::new(chip, config.oe_active_high);
         let mut clk_registers = ClkRegisters::new(chip);
         // TODO: We can drop privileges here.
 
@@ -104,6 +165,17 @@ impl Gpio {
                 return Err(GpioInitializationError::OneWireProtocolEnabled);
             }
 
+            for (module, module_pins) in CONFLICTING_MODULES {
+                let pins = module_pins
+                    .iter()
+                    .copied()
+                    .filter(|&pin| output_bits & gpio_bits!(pin) != 0)
+                    .collect::<Vec<_>>();
+                if !pins.is_empty() && linux_has_module_loaded(module) {
+                    return Err(GpioInitializationError::ConflictingModuleLoaded { module, pins });
+                }
+            }
+
             let k_max_available_bit = 31;
             (0..=k_max_available_bit).for_each(|b| {
                 if output_bits & gpio_bits!(b) != 0 {
@@ -113,18 +185,13 @@ impl Gpio {
         }
         assert!(output_bits == all_used_bits);
 
-        let mut bitplane_timings = Vec::new();
-        let mut timing_ns = config.pwm_lsb_nanoseconds;
-        (0..K_BIT_PLANES).for_each(|b| {
-            bitplane_timings.push(timing_ns);
-            if b >= config.dither_bits {
-                timing_ns *= 2;
-            };
-        });
+        // Let the pin multiplexing settle before the first frame is clocked in.
+        sleep(Duration::from_micros(config.gpio_settle_us));
 
         let pin_pulser = PinPulser::new(
             config.hardware_mapping.output_enable,
-            &bitplane_timings,
+            &config.bitplane_timings_ns(config.pwm_lsb_nanoseconds),
+            &config.bitplane_timings_ns(config.quality_pwm_lsb_nanoseconds()),
             &mut pwm_registers,
             &mut gpio_registers,
             &mut clk_registers,
@@ -133,6 +200,7 @@ impl Gpio {
         let gpio_slowdown = config.slowdown.unwrap_or_else(|| chip.gpio_slowdown());
 
         Ok(Self {
+            _claim: claim,
             gpio_registers,
             time_registers,
             pwm_registers,
@@ -141,6 +209,8 @@ impl Gpio {
             output_bits,
             reserved_bits,
             gpio_slowdown,
+            oe_active_high: config.oe_active_high,
+            strict_blanking: config.strict_blanking,
         })
     }
 
@@ -187,6 +257,32 @@ impl Gpio {
         pin_pulser.wait_pulse_finished(time_registers, pwm_registers);
     }
 
+    /// Switch between the timings of `pwm_lsb_nanoseconds` and `quality_pwm_lsb_nanoseconds`.
+    pub(crate) fn set_quality_mode(&mut self, quality_mode: bool) {
+        self.pin_pulser.set_quality_mode(quality_mode);
+    }
+
+    /// The number of output enable pulses whose end could not be detected and that were cut off.
+    pub(crate) fn pulse_timeouts(&self) -> u64 {
+        self.pin_pulser.timeouts()
+    }
+
+    /// With strict blanking, explicitly drive the output enable to off until [`Gpio::end_blanking`], so that
+    /// nothing is displayed while the row address changes. Does nothing otherwise.
+    pub(crate) fn begin_blanking(&mut self) {
+        if self.strict_blanking {
+            self.pin_pulser
+                .hold_output(&mut self.gpio_registers, !self.oe_active_high);
+        }
+    }
+
+    /// End the blanking started with [`Gpio::begin_blanking`].
+    pub(crate) fn end_blanking(&mut self) {
+        if self.strict_blanking {
+            self.pin_pulser.release_output(&mut self.gpio_registers);
+        }
+    }
+
     pub(crate) fn request_enabled_inputs(&mut self, mut enabled_bits: u32) -> u32 {
         // Remove the bits that are already used otherwise.
         enabled_bits &= !(self.output_bits | self.input_bits | self.reserved_bits);
@@ -201,6 +297,17 @@ impl Gpio {
         enabled_bits
     }
 
+    /// Like [`Gpio::request_enabled_inputs`], but also configures the internal pull resistors of the inputs.
+    pub(crate) fn request_enabled_inputs_with_pull(
+        &mut self,
+        enabled_bits: u32,
+        pull: PullMode,
+    ) -> u32 {
+        let enabled_bits = self.request_enabled_inputs(enabled_bits);
+        self.gpio_registers.set_pull(enabled_bits, pull);
+        enabled_bits
+    }
+
     pub(crate) fn read(&mut self) -> u32 {
         self.gpio_registers.read_pin_level0() & self.input_bits
     }
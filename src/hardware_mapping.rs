@@ -1,4 +1,4 @@
-use std::{error::Error, ops::BitOr, str::FromStr};
+use std::{error::Error, fs::read_to_string, ops::BitOr, path::Path, str::FromStr};
 
 use crate::gpio_bits;
 
@@ -99,35 +99,184 @@ impl FromStr for HardwareMapping {
             "RegularPi1" => Ok(Self::regular_pi1()),
             "Classic" => Ok(Self::classic()),
             "ClassicPi1" => Ok(Self::classic_pi1()),
+            path if Path::new(path).is_file() => Self::load(path),
             _ => Err(format!("'{s}' is not a valid GPIO mapping.").into()),
         }
     }
 }
 
+/// Format the set bits as a comma-separated list of GPIO numbers.
+fn format_pins(bits: u32) -> String {
+    (0..u32::BITS)
+        .filter(|pin| bits & gpio_bits!(pin) != 0)
+        .map(|pin| pin.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse a comma-separated list of GPIO numbers into bits. An empty list means unused.
+fn parse_pins(value: &str) -> Result<u32, Box<dyn Error>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pin| !pin.is_empty())
+        .try_fold(0, |bits, pin| match pin.parse::<u32>() {
+            Ok(pin) if pin < u32::BITS => Ok(bits | gpio_bits!(pin)),
+            _ => Err(format!("'{pin}' is not a valid GPIO number.").into()),
+        })
+}
+
+impl HardwareMapping {
+    /// Serialize the mapping into a pin configuration that can be stored and loaded with
+    /// [`HardwareMapping::from_pin_config`]. Each line assigns a comma-separated list of GPIO numbers to a
+    /// role, e.g. `clock=17` or `chain0_r1=11`.
+    #[must_use]
+    pub fn to_pin_config(&self) -> String {
+        let mut lines = vec![
+            format!("output_enable={}", format_pins(self.output_enable)),
+            format!("clock={}", format_pins(self.clock)),
+            format!("strobe={}", format_pins(self.strobe)),
+            format!("a={}", format_pins(self.a)),
+            format!("b={}", format_pins(self.b)),
+            format!("c={}", format_pins(self.c)),
+            format!("d={}", format_pins(self.d)),
+            format!("e={}", format_pins(self.e)),
+        ];
+        for (chain, color_bits) in self.panels.color_bits.iter().enumerate() {
+            if color_bits.used_bits() == 0 {
+                continue;
+            }
+            let ColorBits {
+                r1,
+                g1,
+                b1,
+                r2,
+                g2,
+                b2,
+            } = color_bits;
+            for (name, bits) in [
+                ("r1", r1),
+                ("g1", g1),
+                ("b1", b1),
+                ("r2", r2),
+                ("g2", g2),
+                ("b2", b2),
+            ] {
+                lines.push(format!("chain{chain}_{name}={}", format_pins(*bits)));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Parse a pin configuration as written by [`HardwareMapping::to_pin_config`]. Roles that are left out
+    /// are unused. Empty lines and lines starting with `#` are ignored. The color pins of up to six chains,
+    /// `chain0` to `chain5`, can be assigned, e.g. to wire a fourth parallel chain to the GPIOs 28 to 31 of a
+    /// Compute Module. The chains are used in order, so don't skip one.
+    pub fn from_pin_config(config: &str) -> Result<Self, Box<dyn Error>> {
+        let mut mapping = Self {
+            output_enable: 0,
+            clock: 0,
+            strobe: 0,
+            a: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            panels: Panels {
+                color_bits: [ColorBits::unused(); 6],
+            },
+        };
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = index + 1;
+            let Some((role, pins)) = line.split_once('=') else {
+                return Err(
+                    format!("Line {line_number}: expected 'role=pins', got '{line}'.").into(),
+                );
+            };
+            let role = role.trim();
+            let bits = parse_pins(pins).map_err(|error| format!("Line {line_number}: {error}"))?;
+            let target = mapping
+                .role_mut(role)
+                .ok_or_else(|| format!("Line {line_number}: '{role}' is not a valid pin role."))?;
+            *target = bits;
+        }
+        Ok(mapping)
+    }
+
+    /// Load a pin configuration from a file. See [`HardwareMapping::from_pin_config`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_pin_config(&read_to_string(path)?)
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut u32> {
+        let bits = match role {
+            "output_enable" => &mut self.output_enable,
+            "clock" => &mut self.clock,
+            "strobe" => &mut self.strobe,
+            "a" => &mut self.a,
+            "b" => &mut self.b,
+            "c" => &mut self.c,
+            "d" => &mut self.d,
+            "e" => &mut self.e,
+            _ => {
+                let (chain, color) = role.strip_prefix("chain")?.split_once('_')?;
+                let color_bits = self
+                    .panels
+                    .color_bits
+                    .get_mut(chain.parse::<usize>().ok()?)?;
+                match color {
+                    "r1" => &mut color_bits.r1,
+                    "g1" => &mut color_bits.g1,
+                    "b1" => &mut color_bits.b1,
+                    "r2" => &mut color_bits.r2,
+                    "g2" => &mut color_bits.g2,
+                    "b2" => &mut color_bits.b2,
+                    _ => return None,
+                }
+            }
+        };
+        Some(bits)
+    }
+}
+
 impl HardwareMapping {
     pub(crate) fn used_bits(&self) -> u32 {
         self.output_enable | self.clock | self.strobe | self.panels.used_bits()
     }
 
     /// Mask of bits while clocking in.
-    pub(crate) fn get_color_clock_mask(&self, parallel: usize) -> u32 {
-        let mut color_clk_mask: u32 = 0;
-        (0..6).for_each(|panel| {
-            if parallel > panel {
-                color_clk_mask |= &self.panels.color_bits[panel].used_bits();
-            }
-        });
-        color_clk_mask |= self.clock;
-        color_clk_mask
+    pub(crate) fn get_color_clock_mask(&self, used_chains: usize) -> u32 {
+        self.panels
+            .color_bits
+            .iter()
+            .take(used_chains)
+            .map(ColorBits::used_bits)
+            .fold(self.clock, BitOr::bitor)
     }
 
+    /// The number of chains that are wired, counted from the first one. The chains are used in order, so a
+    /// chain without color pins ends the count even if later chains have pins, e.g. in a pin configuration
+    /// that skips a chain.
     pub(crate) fn max_parallel_chains(&self) -> usize {
         self.panels
             .color_bits
             .iter()
-            .filter(|p| p.used_bits() > 0)
+            .take_while(|p| p.used_bits() > 0)
             .count()
     }
+
+    /// The color pins of a chain. Chains beyond the wired ones have no pins.
+    pub(crate) fn chain_color_bits(&self, chain: usize) -> ColorBits {
+        self.panels
+            .color_bits
+            .get(chain)
+            .copied()
+            .unwrap_or_else(ColorBits::unused)
+    }
 }
 
 impl HardwareMapping {
@@ -217,6 +366,17 @@ impl HardwareMapping {
         }
     }
 
+    /// Use a different GPIO for the address line E, e.g. for an Adafruit HAT on which line E was wired by hand
+    /// to another pin than GPIO 24.
+    #[must_use]
+    pub const fn with_e_pin(self, pin: u8) -> Self {
+        assert!(pin < 32, "Only GPIO 0 to 31 can be used.");
+        Self {
+            e: gpio_bits!(pin),
+            ..self
+        }
+    }
+
     // An Adafruit HAT with the PWM modification
     #[must_use]
     pub const fn adafruit_hat_pwm() -> Self {
@@ -350,3 +510,52 @@ impl HardwareMapping {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::HardwareMapping;
+    use crate::gpio_bits;
+
+    #[test]
+    fn test_pin_config_round_trip() {
+        for mapping in [
+            HardwareMapping::regular(),
+            HardwareMapping::adafruit_hat_pwm(),
+            HardwareMapping::regular_pi1(),
+            HardwareMapping::classic_pi1(),
+        ] {
+            let config = mapping.to_pin_config();
+            assert_eq!(HardwareMapping::from_pin_config(&config).unwrap(), mapping);
+        }
+    }
+
+    #[test]
+    fn test_max_parallel_chains() {
+        assert_eq!(HardwareMapping::regular().max_parallel_chains(), 3);
+        assert_eq!(HardwareMapping::adafruit_hat().max_parallel_chains(), 1);
+
+        // A fourth chain on the GPIOs above 27, which only Compute Modules have.
+        let mut config = HardwareMapping::regular().to_pin_config();
+        config +=
+            "chain3_r1=28\nchain3_g1=29\nchain3_b1=30\nchain3_r2=31\nchain3_g2=1\nchain3_b2=0\n";
+        let mapping = HardwareMapping::from_pin_config(&config).unwrap();
+        assert_eq!(mapping.max_parallel_chains(), 4);
+        assert_eq!(
+            mapping.get_color_clock_mask(4) & gpio_bits!(28, 29, 30, 31),
+            gpio_bits!(28, 29, 30, 31)
+        );
+        assert_eq!(mapping.get_color_clock_mask(3) & gpio_bits!(28), 0);
+
+        // A skipped chain ends the usable chains.
+        let config = "chain0_r1=5\nchain2_r1=6\n";
+        let mapping = HardwareMapping::from_pin_config(config).unwrap();
+        assert_eq!(mapping.max_parallel_chains(), 1);
+    }
+
+    #[test]
+    fn test_pin_config_errors() {
+        assert!(HardwareMapping::from_pin_config("clock=40").is_err());
+        assert!(HardwareMapping::from_pin_config("chain6_r1=5").is_err());
+        assert!(HardwareMapping::from_pin_config("clock").is_err());
+    }
+}
@@ -20,48 +20,97 @@ impl FromStr for PanelType {
     }
 }
 
-impl PanelType {
-    pub(crate) fn run_init_sequence(self, gpio: &mut Gpio, config: &RGBMatrixConfig) {
-        match self {
-            Self::FM6126 => Self::init_fm6126(gpio, config),
-            Self::FM6127 => Self::init_fm6127(gpio, config),
-        }
-    }
+/// The panel types along a chain, in the order of the canvas columns, for chains that mix panels with different
+/// initialization sequences. Parsed from a comma-separated list in which `none` marks panels that need no
+/// initialization, e.g. `"FM6126,none,none"`. Panels beyond the end of the list need no initialization.
+///
+/// FM6126 and FM6127 panels can't be mixed: the FM6127 needs a third register, and writing it would send a
+/// latch command to the FM6126 panels that their own initialization never sends.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChainPanelTypes(pub Vec<Option<PanelType>>);
+
+impl FromStr for ChainPanelTypes {
+    type Err = Box<dyn Error>;
 
-    fn init_fm6126(gpio: &mut Gpio, config: &RGBMatrixConfig) {
-        let hm = &config.hardware_mapping;
-        let columns = config.cols;
-        let bits_on = hm.panels.used_bits() | hm.a;
-        let bits_off = hm.a;
-        let mask = bits_on | hm.strobe;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .map(|item| match item.to_lowercase().as_str() {
+                "none" | "-" => Ok(None),
+                _ => item.parse().map(Some),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+            .and_then(|panel_types| {
+                if panel_types.is_mixed() {
+                    Err(format!("FM6126 and FM6127 panels can't be mixed in one chain: {s}").into())
+                } else {
+                    Ok(panel_types)
+                }
+            })
+    }
+}
 
-        let init_b12 = 0b0111_1111_1111_1111; // full bright
-        let init_b13 = 0b0000_0000_0100_0000; // panel on
+impl ChainPanelTypes {
+    /// Whether the chain contains both FM6126 and FM6127 panels, which can't be initialized together.
+    pub(crate) fn is_mixed(&self) -> bool {
+        let contains = |panel_type| self.0.contains(&Some(panel_type));
+        contains(PanelType::FM6126) && contains(PanelType::FM6127)
+    }
 
-        gpio.clear_bits(hm.clock | hm.strobe);
+    /// The panel type of each panel in a chain of `chain_length` panels.
+    pub(crate) fn for_chain(&self, chain_length: usize) -> Vec<Option<PanelType>> {
+        (0..chain_length)
+            .map(|panel| self.0.get(panel).copied().flatten())
+            .collect()
+    }
+}
 
-        (0..columns).for_each(|c| {
-            let mut value = if init_b12 & (gpio_bits!(c % 16)) == 0 {
-                bits_off
-            } else {
-                bits_on
-            };
-            if c > columns - 12 {
-                value |= hm.strobe;
-            };
-            gpio.write_masked_bits(value, mask);
-            gpio.set_bits(hm.clock);
-            gpio.clear_bits(hm.clock);
+/// Run the initialization sequences of the given panels, which are shifted in one after the other. Each panel
+/// gets the register values of its own type. Unlike the initialization of a single panel type, which clocks
+/// `cols` columns, this clocks `cols` columns for every panel of the chain. The panels must not mix FM6126 and
+/// FM6127, see [`ChainPanelTypes`].
+pub(crate) fn run_init_sequence(
+    gpio: &mut Gpio,
+    config: &RGBMatrixConfig,
+    panel_types: &[Option<PanelType>],
+) {
+    let hm = &config.hardware_mapping;
+    let panel_columns = config.cols;
+    let columns = panel_columns * panel_types.len();
+    let mask = panel_types
+        .iter()
+        .flatten()
+        .fold(hm.strobe, |mask, panel_type| {
+            let (bits_on, bits_off) = panel_type.data_bits(config);
+            mask | bits_on | bits_off
         });
-        gpio.clear_bits(hm.strobe);
 
+    gpio.clear_bits(hm.clock | hm.strobe);
+
+    // The registers are selected by the number of clocks during which the strobe is held high.
+    for latch_clocks in [12, 13, 11] {
+        let used = panel_types
+            .iter()
+            .flatten()
+            .any(|panel_type| panel_type.register(latch_clocks).is_some());
+        if !used {
+            continue;
+        }
         (0..columns).for_each(|c| {
-            let mut value = if init_b13 & (gpio_bits!(c % 16)) == 0 {
-                bits_off
-            } else {
-                bits_on
+            let mut value = match panel_types[c / panel_columns] {
+                Some(panel_type) => {
+                    let (bits_on, bits_off) = panel_type.data_bits(config);
+                    let register = panel_type.register(latch_clocks).unwrap_or(0);
+                    if register & (gpio_bits!(c % 16)) == 0 {
+                        bits_off
+                    } else {
+                        bits_on
+                    }
+                }
+                None => 0,
             };
-            if c > columns - 13 {
+            if c > columns - latch_clocks {
                 value |= hm.strobe;
             };
             gpio.write_masked_bits(value, mask);
@@ -70,65 +119,51 @@ impl PanelType {
         });
         gpio.clear_bits(hm.strobe);
     }
+}
 
-    /// The FM6217 is very similar to the FM6216. FM6217 adds Register 3 to allow for automatic bad pixel
-    /// suppression.
-    fn init_fm6127(gpio: &mut Gpio, config: &RGBMatrixConfig) {
-        let hm = &config.hardware_mapping;
-        let columns = config.cols;
-        let bits_on = hm.panels.color_bits[0].used_bits() | hm.a;
-        let bits_off = 0;
-        let mask = bits_on | hm.strobe;
-
-        let init_b12 = 0b1111_1111_1100_1110; // register 1
-        let init_b13 = 0b1110_0000_0110_0010; // register 2.
-        let init_b11 = 0b0101_1111_0000_0000; // register 3.
-
-        gpio.clear_bits(hm.clock | hm.strobe);
+impl PanelType {
+    pub(crate) fn run_init_sequence(self, gpio: &mut Gpio, config: &RGBMatrixConfig) {
+        run_init_sequence(gpio, config, &[Some(self)]);
+    }
 
-        (0..columns).for_each(|c| {
-            let mut value = if init_b12 & (gpio_bits!(c % 16)) == 0 {
-                bits_off
-            } else {
-                bits_on
-            };
-            if c > columns - 12 {
-                value |= hm.strobe;
-            };
-            gpio.write_masked_bits(value, mask);
-            gpio.set_bits(hm.clock);
-            gpio.clear_bits(hm.clock);
-        });
-        gpio.clear_bits(hm.strobe);
+    /// The value of the configuration register that is selected by `latch_clocks`, if the panel has it.
+    const fn register(self, latch_clocks: usize) -> Option<u16> {
+        match (self, latch_clocks) {
+            // Full bright.
+            (Self::FM6126, 12) => Some(0b0111_1111_1111_1111),
+            // Panel on.
+            (Self::FM6126, 13) => Some(0b0000_0000_0100_0000),
+            // The FM6217 is very similar to the FM6216. FM6217 adds Register 3 to allow for automatic bad pixel
+            // suppression.
+            (Self::FM6127, 12) => Some(0b1111_1111_1100_1110),
+            (Self::FM6127, 13) => Some(0b1110_0000_0110_0010),
+            (Self::FM6127, 11) => Some(0b0101_1111_0000_0000),
+            _ => None,
+        }
+    }
 
-        (0..columns).for_each(|c| {
-            let mut value = if init_b13 & (gpio_bits!(c % 16)) == 0 {
-                bits_off
-            } else {
-                bits_on
-            };
-            if c > columns - 13 {
-                value |= hm.strobe;
-            };
-            gpio.write_masked_bits(value, mask);
-            gpio.set_bits(hm.clock);
-            gpio.clear_bits(hm.clock);
-        });
-        gpio.clear_bits(hm.strobe);
+    /// The GPIO bits that are written for a set and a cleared register bit.
+    fn data_bits(self, config: &RGBMatrixConfig) -> (u32, u32) {
+        let hm = &config.hardware_mapping;
+        match self {
+            Self::FM6126 => (hm.panels.used_bits() | hm.a, hm.a),
+            Self::FM6127 => (hm.panels.color_bits[0].used_bits() | hm.a, 0),
+        }
+    }
+}
 
-        (0..columns).for_each(|c| {
-            let mut value = if init_b11 & (gpio_bits!(c % 16)) == 0 {
-                bits_off
-            } else {
-                bits_on
-            };
-            if c > columns - 11 {
-                value |= hm.strobe;
-            };
-            gpio.write_masked_bits(value, mask);
-            gpio.set_bits(hm.clock);
-            gpio.clear_bits(hm.clock);
-        });
-        gpio.clear_bits(hm.strobe);
+#[cfg(test)]
+mod tests {
+    use super::{ChainPanelTypes, PanelType};
+
+    #[test]
+    fn test_parse_chain_panel_types() {
+        let panel_types: ChainPanelTypes = "fm6126, none".parse().unwrap();
+        assert_eq!(
+            panel_types.for_chain(3),
+            [Some(PanelType::FM6126), None, None]
+        );
+        assert!("FM6126,FM6127".parse::<ChainPanelTypes>().is_err());
+        assert!("FM6126,FM6000".parse::<ChainPanelTypes>().is_err());
     }
 }
@@ -2,24 +2,43 @@ mod canvas;
 mod chip;
 mod color;
 mod config;
+#[cfg(feature = "drawing")]
+mod console_canvas;
+mod frame_sink;
+mod frame_snapshot;
 mod gpio;
 mod hardware_mapping;
 mod init_sequence;
+#[cfg(feature = "drawing")]
+mod marquee;
 mod multiplex_mapper;
 mod named_pixel_mapper;
+mod panel_profile;
 mod pin_pulser;
 mod pixel_mapper;
 mod registers;
 mod rgb_matrix;
 mod row_address_setter;
+mod terminal_preview;
+mod typed_canvas;
 mod utils;
 
-pub use canvas::{Canvas, LedSequence};
-pub use chip::PiChip;
+pub use canvas::{Canvas, LedSequence, OutOfBounds, RowOrder};
+pub use chip::{BoardInfo, PiChip};
+pub use color::{ChainGains, Palette};
 pub use config::RGBMatrixConfig;
+#[cfg(feature = "drawing")]
+pub use console_canvas::ConsoleCanvas;
 pub use hardware_mapping::HardwareMapping;
-pub use init_sequence::PanelType;
+pub use init_sequence::{ChainPanelTypes, PanelType};
+#[cfg(feature = "drawing")]
+pub use marquee::Marquee;
 pub use multiplex_mapper::MultiplexMapperType;
-pub use rgb_matrix::RGBMatrix;
-pub use row_address_setter::RowAddressSetterType;
-pub use named_pixel_mapper::NamedPixelMapperType;
+pub use named_pixel_mapper::{CoordinateMap, NamedPixelMapperType};
+pub use panel_profile::PanelProfile;
+pub use registers::PullMode;
+pub use rgb_matrix::{
+    FrameBytes, FrameOverrunCallback, InitWarning, RGBMatrix, UpdateThreadStopped,
+};
+pub use row_address_setter::{AddressLine, RowAddressSetterType};
+pub use typed_canvas::TypedCanvas;
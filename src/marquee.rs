@@ -0,0 +1,227 @@
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::{BinaryColor, Rgb888},
+    prelude::{OriginDimensions, Point, RgbColor, Size},
+    text::{renderer::TextRenderer, Baseline, Text},
+    Drawable, Pixel,
+};
+
+use crate::Canvas;
+
+/// The default gap between the end of the text and its repetition, in characters.
+const GAP_CHARACTERS: usize = 3;
+
+/// Text that scrolls from right to left, repeating seamlessly.
+///
+/// The text is rendered once into an off-screen strip, which is rendered again only when the text changes.
+/// Call [`Marquee::step`] once per frame to advance the scrolling and [`Marquee::draw`] to draw the
+/// visible window onto the canvas.
+pub struct Marquee {
+    text: String,
+    font: &'static MonoFont<'static>,
+    color: Rgb888,
+    gap: usize,
+    strip: Strip,
+    offset: usize,
+}
+
+/// The rendered text, one `bool` per pixel in row-major order.
+struct Strip {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl OriginDimensions for Strip {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for Strip {
+    type Color = BinaryColor;
+
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if (0..self.width as i32).contains(&coord.x)
+                && (0..self.height as i32).contains(&coord.y)
+            {
+                self.pixels[coord.y as usize * self.width + coord.x as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Marquee {
+    /// Create a marquee showing `text` in the given font and color. The repetitions of the text are separated by
+    /// the width of three characters.
+    #[must_use]
+    pub fn new(text: &str, font: &'static MonoFont<'static>, color: Rgb888) -> Self {
+        let gap = GAP_CHARACTERS * (font.character_size.width + font.character_spacing) as usize;
+        Self {
+            text: text.to_string(),
+            font,
+            color,
+            gap,
+            strip: Self::render(text, font),
+            offset: 0,
+        }
+    }
+
+    fn render(text: &str, font: &'static MonoFont<'static>) -> Strip {
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        let width = style
+            .measure_string(text, Point::zero(), Baseline::Top)
+            .next_position
+            .x
+            .max(0) as usize;
+        let height = font.character_size.height as usize;
+        let mut strip = Strip {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        };
+        Text::with_baseline(text, Point::zero(), style, Baseline::Top)
+            .draw(&mut strip)
+            .unwrap();
+        strip
+    }
+
+    /// The number of columns after which the scrolling repeats: the width of the text plus the gap.
+    fn period(&self) -> usize {
+        (self.strip.width + self.gap).max(1)
+    }
+
+    /// The displayed text.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Change the displayed text. The text is only rendered again if it differs from the current one. The
+    /// scrolling continues from the current offset.
+    pub fn set_text(&mut self, text: &str) {
+        if self.text != text {
+            self.text = text.to_string();
+            self.strip = Self::render(text, self.font);
+            self.offset %= self.period();
+        }
+    }
+
+    /// Change the color of the text.
+    pub fn set_color(&mut self, color: Rgb888) {
+        self.color = color;
+    }
+
+    /// Change the gap between the end of the text and its repetition, in pixels.
+    pub fn set_gap(&mut self, gap: usize) {
+        self.gap = gap;
+        self.offset %= self.period();
+    }
+
+    /// The height of the marquee in pixels, i.e. the height of the font.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.strip.height
+    }
+
+    /// The column of the text that is drawn at the left edge of the canvas.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Scroll the text one pixel to the left.
+    pub fn step(&mut self) {
+        self.offset = (self.offset + 1) % self.period();
+    }
+
+    /// Draw the visible window of the text across the full width of the canvas, with its top at row `y`. Only
+    /// the pixels of the text are drawn, the background is left unchanged.
+    pub fn draw(&self, canvas: &mut Canvas, y: isize) {
+        let period = self.period();
+        for x in 0..canvas.width() {
+            let column = (self.offset + x) % period;
+            if column >= self.strip.width {
+                continue;
+            }
+            for row in 0..self.strip.height {
+                if self.strip.pixels[row * self.strip.width + column] {
+                    canvas.set_pixel_clipped(
+                        x as isize,
+                        y + row as isize,
+                        self.color.r(),
+                        self.color.g(),
+                        self.color.b(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{mono_font::ascii::FONT_6X10, pixelcolor::Rgb888, prelude::RgbColor};
+
+    use super::Marquee;
+    use crate::{Canvas, RGBMatrixConfig};
+
+    fn lit_columns(canvas: &Canvas) -> Vec<usize> {
+        (0..canvas.width())
+            .filter(|&x| (0..canvas.height()).any(|y| canvas.get_color(x, y) != [0, 0, 0]))
+            .collect()
+    }
+
+    #[test]
+    fn test_marquee_wraps() {
+        let config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            ..Default::default()
+        };
+        let mut marquee = Marquee::new("II", &FONT_6X10, Rgb888::WHITE);
+        marquee.set_gap(4);
+        let period = 2 * 6 + 4;
+        assert_eq!(marquee.height(), 10);
+
+        let mut canvas = Canvas::for_config(&config);
+        marquee.draw(&mut canvas, 0);
+        let initial = lit_columns(&canvas);
+        assert!(!initial.is_empty());
+        // The text repeats every period across the canvas.
+        for &x in &initial {
+            if x + period < canvas.width() {
+                assert!(initial.contains(&(x + period)));
+            }
+        }
+
+        // Each step scrolls the text one pixel to the left, and a full period returns to the start.
+        marquee.step();
+        let mut canvas = Canvas::for_config(&config);
+        marquee.draw(&mut canvas, 0);
+        let shifted = lit_columns(&canvas);
+        for &x in shifted.iter().filter(|&&x| x + 1 < canvas.width()) {
+            assert!(initial.contains(&(x + 1)));
+        }
+        for _ in 1..period {
+            marquee.step();
+        }
+        assert_eq!(marquee.offset(), 0);
+
+        // Changing the text keeps the offset within the new period.
+        for _ in 0..10 {
+            marquee.step();
+        }
+        marquee.set_text("I");
+        assert!(marquee.offset() < 6 + 4);
+        assert_eq!(marquee.text(), "I");
+    }
+}
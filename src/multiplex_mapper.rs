@@ -1,4 +1,4 @@
-use std::{error::Error, str::FromStr};
+use std::{error::Error, mem::replace, str::FromStr};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MultiplexMapperType {
@@ -20,6 +20,11 @@ pub enum MultiplexMapperType {
     P8Outdoor1R1G1B,
     FlippedStripe,
     P10Outdoor32x16HalfScan,
+    /// Outdoor panels that address two scan lines per address line. The parameter is the number of rows in a
+    /// scan block, which are spread over `rows / 2` segments of 8 columns. `P10Outdoor32x16HalfScan` is
+    /// `HalfScan:8`.
+    /// Example: `--multiplexing HalfScan:8`
+    HalfScan(usize),
 }
 
 impl FromStr for MultiplexMapperType {
@@ -45,12 +50,75 @@ impl FromStr for MultiplexMapperType {
             "P8Outdoor1R1G1B" => Ok(Self::P8Outdoor1R1G1B),
             "FlippedStripe" => Ok(Self::FlippedStripe),
             "P10Outdoor32x16HalfScan" => Ok(Self::P10Outdoor32x16HalfScan),
+            other if other.starts_with("HalfScan:") => {
+                let param = &other["HalfScan:".len()..];
+                match param.parse::<usize>() {
+                    Ok(block_rows) if block_rows >= 2 && block_rows % 2 == 0 => {
+                        Ok(Self::HalfScan(block_rows))
+                    }
+                    _ => Err(format!(
+                        "'{param}' is not valid. HalfScan parameter should be an even number of rows"
+                    )
+                    .into()),
+                }
+            }
             other => Err(format!("'{other}' is not a valid GPIO mapping.").into()),
         }
     }
 }
 
 impl MultiplexMapperType {
+    /// All multiplexing mappers that don't take a parameter, e.g. to try them one after another on an unknown
+    /// panel.
+    #[must_use]
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Stripe,
+            Self::Checkered,
+            Self::Spiral,
+            Self::ZStripe08,
+            Self::ZStripe44,
+            Self::ZStripe80,
+            Self::Coreman,
+            Self::Kaler2Scan,
+            Self::P10Z,
+            Self::QiangLiQ8,
+            Self::InversedZStripe,
+            Self::P10Outdoor1R1G1B1,
+            Self::P10Outdoor1R1G1B2,
+            Self::P10Outdoor1R1G1B3,
+            Self::P10Coreman,
+            Self::P8Outdoor1R1G1B,
+            Self::FlippedStripe,
+            Self::P10Outdoor32x16HalfScan,
+        ]
+    }
+
+    /// Whether the multiplexing maps every pixel of a panel with `rows` and `cols` to a distinct position on
+    /// the matrix. Many mappers work in tiles, e.g. of 8 columns, and some are made for a single panel size.
+    pub(crate) fn supports_panel(self, rows: usize, cols: usize) -> bool {
+        let mut mapper = self.create();
+        let [mut matrix_rows, mut matrix_cols] = [rows, cols];
+        mapper.edit_rows_cols(&mut matrix_rows, &mut matrix_cols);
+        // The mappers split a panel into at least two halves of whole scanned rows.
+        if rows == 0
+            || !rows.is_multiple_of(2 * mapper.panel_stretch_factor())
+            || !mapper.supports_size(rows, cols)
+        {
+            return false;
+        }
+        let mut visited = vec![false; matrix_rows * matrix_cols];
+        (0..rows).all(|y| {
+            (0..cols).all(|x| {
+                let [matrix_x, matrix_y] =
+                    mapper.map_visible_to_matrix(matrix_cols, matrix_rows, x, y);
+                matrix_x < matrix_cols
+                    && matrix_y < matrix_rows
+                    && !replace(&mut visited[matrix_y * matrix_cols + matrix_x], true)
+            })
+        })
+    }
+
     pub(crate) fn create(self) -> Box<dyn MultiplexMapper> {
         match self {
             MultiplexMapperType::Stripe => Box::new(StripeMultiplexMapper::new()),
@@ -76,9 +144,8 @@ impl MultiplexMapperType {
             MultiplexMapperType::P10Coreman => Box::new(P10CoremanMapper::new()),
             MultiplexMapperType::P8Outdoor1R1G1B => Box::new(P8Outdoor1R1G1BMultiplexMapper::new()),
             MultiplexMapperType::FlippedStripe => Box::new(FlippedStripeMultiplexMapper::new()),
-            MultiplexMapperType::P10Outdoor32x16HalfScan => {
-                Box::new(P10Outdoor32x16HalfScanMapper::new())
-            }
+            MultiplexMapperType::P10Outdoor32x16HalfScan => Box::new(HalfScanMapper::new(8)),
+            MultiplexMapperType::HalfScan(block_rows) => Box::new(HalfScanMapper::new(block_rows)),
         }
     }
 }
@@ -124,6 +191,12 @@ pub(crate) trait MultiplexMapper {
         [matrix_x, matrix_y]
     }
 
+    /// Whether the mapping can be computed for a panel with `rows` and `cols`, e.g. for mappers made for a
+    /// single panel size. The result still has to be checked with [`MultiplexMapperType::supports_panel`].
+    fn supports_size(&self, _rows: usize, _cols: usize) -> bool {
+        true
+    }
+
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2];
 }
 
@@ -321,6 +394,10 @@ impl MultiplexMapper for SpiralMultiplexMapper {
         self.stretch_factor
     }
 
+    fn supports_size(&self, _rows: usize, cols: usize) -> bool {
+        cols >= 4
+    }
+
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
         let is_top_stripe = (y % (self.panel_rows() / 2)) < self.panel_rows() / 4;
         let panel_quarter = self.panel_cols() / 4;
@@ -431,29 +508,25 @@ impl MultiplexMapper for CoremanMapper {
         self.stretch_factor
     }
 
+    fn supports_size(&self, _rows: usize, cols: usize) -> bool {
+        cols >= 2
+    }
+
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
+        // The first quarter of each half of the panel, i.e. rows 0 to 7 and 16 to 23 of a 32 row panel, is
+        // shifted in before the second quarter. Both quarters share the scanned rows.
+        let is_top_check = (y % (self.panel_rows() / 2)) < self.panel_rows() / 4;
         let is_left_check = x < self.panel_cols() / 2;
-
-        if (y <= 7) || (16..=23).contains(&y) {
-            let matrix_x =
-                ((x / (self.panel_cols() / 2)) * self.panel_cols()) + (x % (self.panel_cols() / 2));
-            let matrix_y = if (y & (self.panel_rows() / 4)) == 0 {
-                (y / (self.panel_rows() / 2)) * (self.panel_rows() / 4)
-                    + (y % (self.panel_rows() / 4))
-            } else {
-                0
-            };
-            [matrix_x, matrix_y]
+        let matrix_x = if is_top_check {
+            (x / (self.panel_cols() / 2)) * self.panel_cols() + x % (self.panel_cols() / 2)
+        } else if is_left_check {
+            x + self.panel_cols() / 2
         } else {
-            let matrix_x = if is_left_check {
-                x + self.panel_cols() / 2
-            } else {
-                x + self.panel_cols()
-            };
-            let matrix_y = (y / (self.panel_rows() / 2)) * (self.panel_rows() / 4)
-                + y % (self.panel_rows() / 4);
-            [matrix_x, matrix_y]
-        }
+            x + self.panel_cols()
+        };
+        let matrix_y =
+            (y / (self.panel_rows() / 2)) * (self.panel_rows() / 4) + y % (self.panel_rows() / 4);
+        [matrix_x, matrix_y]
     }
 }
 
@@ -495,15 +568,21 @@ impl MultiplexMapper for Kaler2ScanMapper {
     }
 
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
-        // Now we have a 128x4 matrix
-        let offset: isize = if ((y % 4) / 2) == 0 { -1 } else { 1 }; // Add or subtract
-        let delta_offset: isize = if offset < 0 { 7 } else { 8 };
-        let delta_column: isize = if ((y % 8) / 4) == 0 { 64 } else { 0 };
+        // Now we have a matrix of four times the columns, e.g. 128x4 for a 32x16 panel. Each block of 8 columns
+        // is shifted into 16 columns, reversed into the first 8 or forward into the last 8.
+        let block_column = if ((y % 4) / 2) == 0 {
+            7 - x % 8
+        } else {
+            8 + x % 8
+        };
+        let delta_column = if ((y % 8) / 4) == 0 {
+            2 * self.panel_cols()
+        } else {
+            0
+        };
 
         let matrix_y = y % 2 + (y / 8) * 2;
-        let matrix_x =
-            (delta_column + (16 * (x as isize / 8)) + delta_offset + ((x as isize % 8) * offset))
-                as usize;
+        let matrix_x = delta_column + 16 * (x / 8) + block_column;
         [matrix_x, matrix_y]
     }
 }
@@ -545,6 +624,10 @@ impl MultiplexMapper for P10MapperZ {
         self.stretch_factor
     }
 
+    fn supports_size(&self, rows: usize, cols: usize) -> bool {
+        [rows, cols] == [16, 32]
+    }
+
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
         let y_comp = match y {
             0 | 1 | 8 | 9 => 127,
@@ -894,23 +977,27 @@ impl MultiplexMapper for P10CoremanMapper {
     }
 }
 
-pub(crate) struct P10Outdoor32x16HalfScanMapper {
+/// Panels that drive two scan lines per address line. Each block of `block_rows` rows is stored in two matrix
+/// rows, with pairs of rows laid out as serpentine segments of 8 columns, starting from the last segment.
+pub(crate) struct HalfScanMapper {
     panel_rows: usize,
     panel_cols: usize,
     stretch_factor: usize,
+    block_rows: usize,
 }
 
-impl P10Outdoor32x16HalfScanMapper {
-    pub(crate) fn new() -> Self {
+impl HalfScanMapper {
+    pub(crate) fn new(block_rows: usize) -> Self {
         Self {
             panel_rows: 0,
             panel_cols: 0,
-            stretch_factor: 4,
+            stretch_factor: block_rows / 2,
+            block_rows,
         }
     }
 }
 
-impl MultiplexMapper for P10Outdoor32x16HalfScanMapper {
+impl MultiplexMapper for HalfScanMapper {
     fn panel_rows(&self) -> usize {
         self.panel_rows
     }
@@ -932,22 +1019,14 @@ impl MultiplexMapper for P10Outdoor32x16HalfScanMapper {
     }
 
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
-        let base = (x / 8) * 32;
-        let reverse = (y % 4) / 2 == 0;
-        let offset = (3 - ((y % 8) / 2)) * 8;
+        let segments = self.stretch_factor;
+        let base = (x / 8) * 8 * segments;
+        let segment = (y % self.block_rows) / 2;
+        let reverse = segment % 2 == 0;
+        let offset = (segments - 1 - segment) * 8;
         let dx = x % 8;
 
-        let matrix_y = if y / 8 == 0 {
-            if y % 2 == 0 {
-                0
-            } else {
-                1
-            }
-        } else if y % 2 == 0 {
-            2
-        } else {
-            3
-        };
+        let matrix_y = (y / self.block_rows) * 2 + y % 2;
         let matrix_x = base
             + if reverse {
                 offset + (7 - dx)
@@ -1002,8 +1081,12 @@ impl MultiplexMapper for P8Outdoor1R1G1BMultiplexMapper {
         self.stretch_factor
     }
 
+    fn supports_size(&self, rows: usize, cols: usize) -> bool {
+        [rows, cols] == [20, 40]
+    }
+
     fn map_single_panel(&self, x: usize, y: usize) -> [usize; 2] {
-        let vblock_is_even = (y / P10_TILE_HEIGHT) % 2 == 0;
+        let vblock_is_even = (y / P8_TILE_HEIGHT) % 2 == 0;
         let matrix_x = if vblock_is_even {
             P8_TILE_WIDTH * (1 + P8_TILE_WIDTH - 2 * (x / P8_TILE_WIDTH)) + P8_TILE_WIDTH
                 - (x % P8_TILE_WIDTH)
@@ -1019,3 +1102,105 @@ impl MultiplexMapper for P8Outdoor1R1G1BMultiplexMapper {
         [matrix_x, matrix_y]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MultiplexMapperType;
+
+    /// Every visible pixel of a panel must end up on a distinct matrix pixel.
+    fn assert_one_to_one(mapper_type: MultiplexMapperType, rows: usize, cols: usize) {
+        assert!(
+            mapper_type.supports_panel(rows, cols),
+            "{mapper_type:?} doesn't map a {cols}x{rows} panel one to one"
+        );
+    }
+
+    #[test]
+    fn test_stripe_family() {
+        let stripe_family = [
+            MultiplexMapperType::Stripe,
+            MultiplexMapperType::FlippedStripe,
+            MultiplexMapperType::ZStripe08,
+            MultiplexMapperType::ZStripe44,
+            MultiplexMapperType::ZStripe80,
+            MultiplexMapperType::InversedZStripe,
+        ];
+        for mapper_type in stripe_family {
+            for [rows, cols] in [[16, 32], [32, 64], [64, 64]] {
+                assert_one_to_one(mapper_type, rows, cols);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flipped_stripe_mirrors_stripe() {
+        for [rows, cols] in [[16, 32], [32, 64], [64, 64]] {
+            let [mut stripe, mut flipped] = [
+                MultiplexMapperType::Stripe.create(),
+                MultiplexMapperType::FlippedStripe.create(),
+            ];
+            let [mut matrix_rows, mut matrix_cols] = [rows, cols];
+            stripe.edit_rows_cols(&mut matrix_rows, &mut matrix_cols);
+            flipped.edit_rows_cols(&mut rows.clone(), &mut cols.clone());
+            for y in 0..rows {
+                for x in 0..cols {
+                    let [stripe_x, stripe_y] =
+                        stripe.map_visible_to_matrix(matrix_cols, matrix_rows, x, y);
+                    let [flipped_x, flipped_y] =
+                        flipped.map_visible_to_matrix(matrix_cols, matrix_rows, x, y);
+                    assert_eq!(flipped_y, stripe_y);
+                    assert_eq!(flipped_x, (stripe_x + cols) % matrix_cols);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_coreman() {
+        for [rows, cols] in [[32, 32], [32, 64], [64, 64]] {
+            assert_one_to_one(MultiplexMapperType::Coreman, rows, cols);
+        }
+    }
+
+    #[test]
+    fn test_kaler_2_scan() {
+        for [rows, cols] in [[16, 32], [32, 32], [16, 64], [32, 64], [16, 128], [32, 128]] {
+            assert_one_to_one(MultiplexMapperType::Kaler2Scan, rows, cols);
+        }
+    }
+
+    #[test]
+    fn test_all_at_panel_size() {
+        for &mapper_type in MultiplexMapperType::all() {
+            let [rows, cols] = match mapper_type {
+                MultiplexMapperType::QiangLiQ8 | MultiplexMapperType::P8Outdoor1R1G1B => [20, 40],
+                MultiplexMapperType::Coreman => [32, 32],
+                _ => [16, 32],
+            };
+            assert_one_to_one(mapper_type, rows, cols);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_panels() {
+        assert!(MultiplexMapperType::Stripe.supports_panel(16, 80));
+        assert!(MultiplexMapperType::ZStripe08.supports_panel(16, 80));
+        assert!(!MultiplexMapperType::ZStripe08.supports_panel(16, 36));
+        assert!(!MultiplexMapperType::P10Z.supports_panel(16, 64));
+        assert!(!MultiplexMapperType::P8Outdoor1R1G1B.supports_panel(20, 80));
+        assert!(!MultiplexMapperType::Kaler2Scan.supports_panel(18, 32));
+    }
+
+    #[test]
+    fn test_p8_outdoor_40x20() {
+        assert_one_to_one(MultiplexMapperType::P8Outdoor1R1G1B, 20, 40);
+    }
+
+    #[test]
+    fn test_half_scan() {
+        assert_one_to_one(MultiplexMapperType::P10Outdoor32x16HalfScan, 16, 32);
+        assert_one_to_one("HalfScan:4".parse().unwrap(), 16, 32);
+        assert_one_to_one("HalfScan:16".parse().unwrap(), 32, 64);
+        assert!("HalfScan:3".parse::<MultiplexMapperType>().is_err());
+    }
+}
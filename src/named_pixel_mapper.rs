@@ -1,4 +1,12 @@
-use std::{error::Error, str::FromStr};
+use std::{
+    error::Error,
+    fmt::{Debug, Formatter},
+    fs::read_to_string,
+    path::Path,
+    str::FromStr,
+};
+
+use crate::config::MAX_PIXELS;
 
 /// Enum representing different pixel mapping options for mapping the logical layout of your boards
 /// to your physical arrangement. These options allow you to customize the mapping to match your unique setup.
@@ -8,7 +16,7 @@ use std::{error::Error, str::FromStr};
 /// You can apply multiple mappers in your configuration, and they will be applied in the order you specify.
 /// For example, to first mirror the panels horizontally and then rotate the resulting screen,
 /// You can use `--pixelmapper Mirror:H --pixelmapper Rotate:90`
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum NamedPixelMapperType {
     /// The "Mirror" mapper allows you to mirror the output either horizontally or vertically.
     /// Specify 'H' for horizontal mirroring or 'V' for vertical mirroring as a parameter after a colon.
@@ -43,6 +51,134 @@ pub enum NamedPixelMapperType {
     ///   [<][<][<][<]  }--- Pi connector #2
     ///   [>][>][>][>]
     UMapper,
+    /// The "Custom" mapper places each visible pixel at an arbitrary pixel of the matrix, as listed in a
+    /// [`CoordinateMap`], e.g. for panels that are scattered across an art piece instead of forming a grid.
+    /// Visible pixels that are not listed stay dark.
+    /// Specify the path of the coordinate map file as a parameter after a colon.
+    /// Example: `--pixelmapper Custom:layout.txt`
+    Custom(CoordinateMap),
+}
+
+/// A table that maps visible pixels to pixels of the matrix, for [`NamedPixelMapperType::Custom`]. The visible
+/// canvas spans the listed visible pixels, and each pixel of the matrix can be shown at most once.
+///
+/// The matrix coordinates are those of the canvas the mapper is applied to, i.e. for the first mapper
+/// `x = panel * cols + panel_x` along a chain and `y = chain * rows + panel_y` for parallel chains.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CoordinateMap {
+    width: usize,
+    height: usize,
+    /// The matrix pixel of each visible pixel in row-major order, `None` for holes.
+    table: Vec<Option<[usize; 2]>>,
+}
+
+impl Debug for CoordinateMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoordinateMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CoordinateMap {
+    /// Create a map from `(visible, matrix)` pairs of `[x, y]` coordinates. Fails if a visible or a matrix pixel
+    /// is listed twice.
+    pub fn new(
+        pairs: impl IntoIterator<Item = ([usize; 2], [usize; 2])>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let pairs: Vec<_> = pairs.into_iter().collect();
+        let width = pairs.iter().map(|([x, _], _)| x + 1).max().unwrap_or(0);
+        let height = pairs.iter().map(|([_, y], _)| y + 1).max().unwrap_or(0);
+        if width == 0 || width.saturating_mul(height) > MAX_PIXELS {
+            return Err(format!(
+                "A coordinate map needs between 1 and {MAX_PIXELS} visible pixels."
+            )
+            .into());
+        }
+        let mut table = vec![None; width * height];
+        let mut matrix_pixels = pairs.iter().map(|(_, matrix)| *matrix).collect::<Vec<_>>();
+        matrix_pixels.sort_unstable();
+        if let Some([x, y]) = matrix_pixels
+            .windows(2)
+            .find(|pixels| pixels[0] == pixels[1])
+            .map(|pixels| pixels[0])
+        {
+            return Err(format!("The matrix pixel {x},{y} is mapped more than once.").into());
+        }
+        for ([x, y], matrix) in pairs {
+            if table[y * width + x].replace(matrix).is_some() {
+                return Err(format!("The visible pixel {x},{y} is mapped more than once.").into());
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            table,
+        })
+    }
+
+    /// Parse a map with one visible pixel per line, followed by the matrix pixel it is shown on, e.g.
+    /// `0,0=64,31`. Empty lines and lines starting with `#` are ignored.
+    pub fn from_table(table: &str) -> Result<Self, Box<dyn Error>> {
+        fn parse_pixel(pixel: &str) -> Option<[usize; 2]> {
+            let (x, y) = pixel.split_once(',')?;
+            Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+        }
+
+        let mut pairs = Vec::new();
+        for (index, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pair = line
+                .split_once('=')
+                .and_then(|(visible, matrix)| Some((parse_pixel(visible)?, parse_pixel(matrix)?)));
+            let Some(pair) = pair else {
+                return Err(format!(
+                    "Line {}: expected 'x,y=matrix_x,matrix_y', got '{line}'.",
+                    index + 1
+                )
+                .into());
+            };
+            pairs.push(pair);
+        }
+        Self::new(pairs)
+    }
+
+    /// Load a map from a file. See [`CoordinateMap::from_table`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_table(&read_to_string(path)?)
+    }
+
+    /// The width of the visible canvas.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the visible canvas.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The matrix pixel of a visible pixel, `None` if it is not mapped.
+    fn get(&self, x: usize, y: usize) -> Option<[usize; 2]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.table[y * self.width + x]
+    }
+
+    /// Whether all matrix pixels lie within a matrix of the given size.
+    pub(crate) fn fits(&self, matrix_width: usize, matrix_height: usize) -> bool {
+        self.table
+            .iter()
+            .flatten()
+            .all(|&[x, y]| x < matrix_width && y < matrix_height)
+    }
 }
 
 impl FromStr for NamedPixelMapperType {
@@ -71,6 +207,7 @@ impl FromStr for NamedPixelMapperType {
                     }
                     Err("Rotation angle is missing or invalid".into())
                 }
+                "Custom" => Ok(Self::Custom(CoordinateMap::load(param)?)),
                 other => Err(format!("'{other}' is not a valid Pixel mapping.").into()),
             }
         } else if s == "U-mapper" {
@@ -82,14 +219,35 @@ impl FromStr for NamedPixelMapperType {
 }
 
 impl NamedPixelMapperType {
-    pub(crate) fn create(self, chain: usize, parallel: usize) -> Box<dyn NamedPixelMapper> {
-        match self {
+    /// Parse a comma-separated list of pixel mappers, e.g. `"Mirror:H,Rotate:90"`. The error names the
+    /// element that could not be parsed.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        s.split(',')
+            .map(str::trim)
+            .enumerate()
+            .map(|(index, item)| {
+                item.parse::<Self>().map_err(|error| {
+                    format!("Pixel mapper {} ('{item}'): {error}", index + 1).into()
+                })
+            })
+            .collect()
+    }
+
+    /// Create the mapper for a matrix with `chain` panels in each of `parallel` chains. Fails with the reason if
+    /// the mapper doesn't support the arrangement of the panels.
+    pub(crate) fn create(
+        self,
+        chain: usize,
+        parallel: usize,
+    ) -> Result<Box<dyn NamedPixelMapper>, String> {
+        Ok(match self {
+            NamedPixelMapperType::Custom(map) => Box::new(CoordinateMapper { map }),
             NamedPixelMapperType::Mirror(horizontal) => Box::new(MirrorPixelMapper { horizontal }),
             NamedPixelMapperType::Rotate(angle) => Box::new(RotatePixelMapper { angle }),
             NamedPixelMapperType::UMapper => {
-                Box::new(UArrangeMapper::new_with_parameters(chain, parallel))
+                Box::new(UArrangeMapper::new_with_parameters(chain, parallel)?)
             }
-        }
+        })
     }
 }
 
@@ -107,6 +265,11 @@ pub(crate) trait NamedPixelMapper {
         visible_x: usize,
         visible_y: usize,
     ) -> [usize; 2];
+
+    /// Whether a visible pixel is shown on the matrix. Pixels that are not stay dark.
+    fn is_visible(&self, _visible_x: usize, _visible_y: usize) -> bool {
+        true
+    }
 }
 
 struct MirrorPixelMapper {
@@ -168,22 +331,23 @@ struct UArrangeMapper {
 }
 
 impl UArrangeMapper {
-    fn new_with_parameters(chain: usize, parallel: usize) -> Self {
-        assert!(
-            chain >= 2,
-            "U-mapper: need at least '--chain_length 4' for useful folding"
-        );
-        assert!(
-            chain % 2 == 0,
-            "U-mapper: Chain (--chain_length) needs to be divisible by two"
-        );
-        Self { parallel }
+    fn new_with_parameters(chain: usize, parallel: usize) -> Result<Self, String> {
+        if chain < 2 {
+            return Err("need at least '--chain-length 2' for useful folding.".to_string());
+        }
+        if chain % 2 != 0 {
+            return Err(format!(
+                "the chain length (--chain-length) {chain} needs to be divisible by two."
+            ));
+        }
+        Ok(Self { parallel })
     }
 }
 
 impl NamedPixelMapper for UArrangeMapper {
     fn get_size_mapping(&self, matrix_width: usize, matrix_height: usize) -> [usize; 2] {
-        let visible_width = (matrix_width / 64) * 32; // Div at 32px boundary
+        // Half of the chain is folded below the other half.
+        let visible_width = matrix_width / 2;
         let visible_height = 2 * matrix_height;
         if matrix_height % self.parallel != 0 {
             eprintln!(
@@ -202,7 +366,7 @@ impl NamedPixelMapper for UArrangeMapper {
         y: usize,
     ) -> [usize; 2] {
         let panel_height = matrix_height / self.parallel;
-        let visible_width = (matrix_width / 64) * 32;
+        let visible_width = matrix_width / 2;
         let slab_height = 2 * panel_height; // one folded u-shape
         let base_y = (y / slab_height) * panel_height;
         let y_in_slab = y % slab_height;
@@ -218,3 +382,27 @@ impl NamedPixelMapper for UArrangeMapper {
         [matrix_x, base_y + matrix_y]
     }
 }
+
+struct CoordinateMapper {
+    map: CoordinateMap,
+}
+
+impl NamedPixelMapper for CoordinateMapper {
+    fn get_size_mapping(&self, _matrix_width: usize, _matrix_height: usize) -> [usize; 2] {
+        [self.map.width(), self.map.height()]
+    }
+
+    fn map_visible_to_matrix(
+        &self,
+        matrix_width: usize,
+        matrix_height: usize,
+        x: usize,
+        y: usize,
+    ) -> [usize; 2] {
+        self.map.get(x, y).unwrap_or([matrix_width, matrix_height])
+    }
+
+    fn is_visible(&self, x: usize, y: usize) -> bool {
+        self.map.get(x, y).is_some()
+    }
+}
@@ -0,0 +1,99 @@
+use std::{error::Error, str::FromStr};
+
+use crate::{
+    canvas::LedSequence, init_sequence::PanelType, multiplex_mapper::MultiplexMapperType,
+    row_address_setter::RowAddressSetterType, RGBMatrixConfig,
+};
+
+/// A named bundle of the settings that a particular kind of panel needs. Applying a profile overrides the
+/// settings it specifies and leaves the others untouched.
+///
+/// New profiles can be added to [`PanelProfile::ALL`]. The name should follow what is printed on the panel,
+/// e.g. `p10-outdoor-32x16-halfscan`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PanelProfile {
+    pub name: &'static str,
+    pub multiplexing: Option<MultiplexMapperType>,
+    pub row_setter: Option<RowAddressSetterType>,
+    pub led_sequence: Option<LedSequence>,
+    pub panel_type: Option<PanelType>,
+}
+
+impl PanelProfile {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            multiplexing: None,
+            row_setter: None,
+            led_sequence: None,
+            panel_type: None,
+        }
+    }
+
+    /// All known profiles.
+    pub const ALL: &'static [PanelProfile] = &[
+        PanelProfile {
+            panel_type: Some(PanelType::FM6126),
+            ..Self::new("fm6126a")
+        },
+        PanelProfile {
+            panel_type: Some(PanelType::FM6127),
+            ..Self::new("fm6127")
+        },
+        PanelProfile {
+            multiplexing: Some(MultiplexMapperType::P10Outdoor32x16HalfScan),
+            ..Self::new("p10-outdoor-32x16-halfscan")
+        },
+        PanelProfile {
+            multiplexing: Some(MultiplexMapperType::P8Outdoor1R1G1B),
+            ..Self::new("p8-outdoor-40x20")
+        },
+        PanelProfile {
+            multiplexing: Some(MultiplexMapperType::Checkered),
+            row_setter: Some(RowAddressSetterType::Direct),
+            ..Self::new("indoor-1-16-checkered")
+        },
+        PanelProfile {
+            row_setter: Some(RowAddressSetterType::SM5266),
+            ..Self::new("sm5266")
+        },
+        PanelProfile {
+            row_setter: Some(RowAddressSetterType::ABCShiftRegister),
+            ..Self::new("abc-shift-register")
+        },
+    ];
+
+    pub(crate) fn apply(&self, config: &mut RGBMatrixConfig) {
+        if let Some(multiplexing) = self.multiplexing {
+            config.multiplexing = Some(multiplexing);
+        }
+        if let Some(row_setter) = self.row_setter {
+            config.row_setter = row_setter;
+        }
+        if let Some(led_sequence) = self.led_sequence {
+            config.led_sequence = led_sequence;
+        }
+        if let Some(panel_type) = self.panel_type {
+            config.panel_type = Some(panel_type);
+        }
+    }
+}
+
+impl FromStr for PanelProfile {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|profile| profile.name).collect();
+                format!(
+                    "'{s}' is not a known panel profile. Known profiles: {}",
+                    names.join(", ")
+                )
+                .into()
+            })
+    }
+}
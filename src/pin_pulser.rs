@@ -5,54 +5,103 @@ use crate::{
 
 const PWM_BASE_TIME_NS: u32 = 2;
 
+/// How long past its expected end a pulse may take before the FIFO is no longer polled and the PWM is reset.
+const PULSE_TIMEOUT_US: u64 = 1000;
+
 struct Pulse {
     start_time: u64,
     sleep_hint_us: u32,
 }
 
-pub(crate) struct PinPulser {
+/// How long to pulse each bit plane.
+struct PulseTimings {
     /// Hints how long to sleep.
     sleep_hints_us: Vec<u32>,
     /// Pulse period for each bit plane.
     pulse_periods: Vec<u32>,
+}
+
+impl PulseTimings {
+    fn new(bitplane_timings_ns: &[u32], time_base: u32) -> Self {
+        Self {
+            sleep_hints_us: bitplane_timings_ns.iter().map(|t| t / 1000).collect(),
+            pulse_periods: bitplane_timings_ns
+                .iter()
+                .map(|timing| 2 * timing / time_base)
+                .collect(),
+        }
+    }
+}
+
+pub(crate) struct PinPulser {
+    /// The timings of the normal and the quality mode.
+    timings: [PulseTimings; 2],
+    /// Whether the quality timings are used.
+    quality_mode: bool,
     /// The current pulse.
     current_pulse: Option<Pulse>,
+    /// The number of pulses whose end was not detected in time.
+    timeouts: u64,
+    /// The output enable pin and its PWM function.
+    pin: u8,
+    pwm_function: GPIOFunction,
 }
 
 impl PinPulser {
     pub(crate) fn new(
         pins: u32,
         bitplane_timings_ns: &[u32],
+        quality_bitplane_timings_ns: &[u32],
         pwm_registers: &mut PWMRegisters,
         gpio_registers: &mut GPIORegisters,
         clk_registers: &mut ClkRegisters,
     ) -> Self {
-        let sleep_hints_us = bitplane_timings_ns.iter().map(|t| t / 1000).collect();
-
         let time_base = bitplane_timings_ns[0];
 
-        if pins == gpio_bits!(18) {
-            // Set GPIO 18 to PWM0 mode
-            gpio_registers.select_function(18, GPIOFunction::Alt5);
+        let (pin, pwm_function) = if pins == gpio_bits!(18) {
+            // GPIO 18 in PWM0 mode
+            (18, GPIOFunction::Alt5)
         } else if pins == gpio_bits!(12) {
-            // Set GPIO 12 to PWM0 mode
-            gpio_registers.select_function(12, GPIOFunction::Alt0);
+            // GPIO 12 in PWM0 mode
+            (12, GPIOFunction::Alt0)
         } else {
             unreachable!()
-        }
+        };
+        gpio_registers.select_function(pin, pwm_function);
 
         pwm_registers.reset_pwm();
         clk_registers.init_pwm_divider((time_base / 2) / PWM_BASE_TIME_NS);
-        let pulse_periods = bitplane_timings_ns
-            .iter()
-            .map(|timing| 2 * timing / time_base)
-            .collect();
+        // Both timings share the clock divider, so the quality timings are expressed in periods of the normal
+        // time base.
+        let timings = [
+            PulseTimings::new(bitplane_timings_ns, time_base),
+            PulseTimings::new(quality_bitplane_timings_ns, time_base),
+        ];
 
         Self {
-            sleep_hints_us,
-            pulse_periods,
+            timings,
+            quality_mode: false,
             current_pulse: None,
+            timeouts: 0,
+            pin,
+            pwm_function,
+        }
+    }
+
+    /// Take the output enable pin away from the PWM and drive it to the given level, which must be the inactive
+    /// one, until [`PinPulser::release_output`] is called.
+    pub(crate) fn hold_output(&self, gpio_registers: &mut GPIORegisters, high: bool) {
+        if high {
+            gpio_registers.write_set_bits(gpio_bits!(self.pin));
+        } else {
+            gpio_registers.write_clr_bits(gpio_bits!(self.pin));
         }
+        gpio_registers.select_function(self.pin, GPIOFunction::Output);
+    }
+
+    /// Hand the output enable pin back to the PWM.
+    pub(crate) fn release_output(&self, gpio_registers: &mut GPIORegisters) {
+        gpio_registers.select_function(self.pin, self.pwm_function);
     }
 
     pub(crate) fn send_pulse(
@@ -61,14 +110,16 @@ impl PinPulser {
         pwm_registers: &mut PWMRegisters,
         time_registers: &mut TimeRegisters,
     ) {
-        if self.pulse_periods[bitplane] < 16 {
-            pwm_registers.set_pwm_pulse_period(self.pulse_periods[bitplane]);
-            pwm_registers.push_fifo(self.pulse_periods[bitplane]);
+        let timings = &self.timings[usize::from(self.quality_mode)];
+        let pulse_period = timings.pulse_periods[bitplane];
+        if pulse_period < 16 {
+            pwm_registers.set_pwm_pulse_period(pulse_period);
+            pwm_registers.push_fifo(pulse_period);
         } else {
             // Keep the actual range as short as possible, as we have to wait for one full period of these in
             // the zero phase. The hardware can't deal with values < 2, so only do this when we have have
             // enough of these.
-            let period_fraction = self.pulse_periods[bitplane] / 8;
+            let period_fraction = pulse_period / 8;
             pwm_registers.set_pwm_pulse_period(period_fraction);
             for _ in 0..8 {
                 pwm_registers.push_fifo(period_fraction);
@@ -87,7 +138,7 @@ impl PinPulser {
 
         self.current_pulse = Some(Pulse {
             start_time: time_registers.get_time(),
-            sleep_hint_us: self.sleep_hints_us[bitplane],
+            sleep_hint_us: timings.sleep_hints_us[bitplane],
         });
         pwm_registers.enable_pwm();
     }
@@ -105,11 +156,28 @@ impl PinPulser {
         let remaining_time_us = u64::from(pulse.sleep_hint_us).saturating_sub(already_elapsed_us);
         time_registers.sleep_at_most(remaining_time_us);
 
+        // If the FIFO never reports empty, resetting the PWM below still ends the pulse, so the row is blanked
+        // before the next strobe either way.
+        let deadline = pulse.start_time + u64::from(pulse.sleep_hint_us) + PULSE_TIMEOUT_US;
         while !pwm_registers.fifo_empty() {
+            if time_registers.get_time() > deadline {
+                self.timeouts += 1;
+                break;
+            }
             // busy wait until done.
             std::thread::yield_now();
         }
 
         pwm_registers.reset_pwm();
     }
+
+    /// Switch between the normal and the quality timings. Takes effect with the next pulse.
+    pub(crate) fn set_quality_mode(&mut self, quality_mode: bool) {
+        self.quality_mode = quality_mode;
+    }
+
+    /// The number of pulses whose end was not detected within [`PULSE_TIMEOUT_US`] and that were cut off.
+    pub(crate) fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
 }
@@ -19,6 +19,11 @@ pub(crate) trait PixelMapper {
         visible_x: usize,
         visible_y: usize,
     ) -> [usize; 2];
+
+    /// Whether a visible pixel is shown on the matrix at all. Pixels that are not stay dark.
+    fn is_visible(&self, _visible_x: usize, _visible_y: usize) -> bool {
+        true
+    }
 }
 
 pub(crate) struct MultiplexMapperWrapper(pub(crate) Box<dyn MultiplexMapper>);
@@ -60,4 +65,8 @@ impl PixelMapper for NamedPixelMapperWrapper {
         // Delegate the call to the underlying NamedPixelMapper
         self.0.map_visible_to_matrix(old_width, old_height, x, y)
     }
+
+    fn is_visible(&self, x: usize, y: usize) -> bool {
+        self.0.is_visible(x, y)
+    }
 }
@@ -1,6 +1,8 @@
 use std::{
+    error::Error,
     fs::OpenOptions,
     rc::Rc,
+    str::FromStr,
     thread::{sleep, yield_now},
     time::Duration,
 };
@@ -36,11 +38,17 @@ impl<T> MmapPtr<T> {
 
 // General Purpose IO
 const GP_OFFSET: u64 = 0x0020_0000;
-const GP_SIZE_BYTES: usize = 41 * std::mem::size_of::<u32>();
+const GP_SIZE_BYTES: usize = 61 * std::mem::size_of::<u32>();
 const GP_FSEL0: usize = 0x0;
 const GP_SET0: usize = 0x1C;
 const GP_CLR0: usize = 0x28;
 const GP_LEV0: usize = 0x34;
+// Pull-up/down on chips before the BCM2711.
+const GP_PUD: usize = 0x94;
+const GP_PUDCLK0: usize = 0x98;
+// Pull-up/down on the BCM2711, two bits per pin.
+const GP_PUP_PDN_CNTRL0: usize = 0xE4;
+const GP_PUP_PDN_CNTRL1: usize = 0xE8;
 
 struct GPIOFunctionSelectRegisters {
     registers_by_function: [MmapPtr<u32>; 6],
@@ -100,6 +108,30 @@ impl GPIOFunction {
     }
 }
 
+/// The internal pull resistor of an input pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PullMode {
+    /// No pull resistor, the pin is floating if nothing drives it.
+    Off,
+    /// Pull the pin high, e.g. for buttons that connect to ground.
+    Up,
+    /// Pull the pin low, e.g. for buttons that connect to 3.3V.
+    Down,
+}
+
+impl FromStr for PullMode {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            _ => Err(format!("'{s}' is not a valid pull mode.").into()),
+        }
+    }
+}
+
 pub fn mmap_bcm_register(chip: PiChip, offset: u64, size_bytes: usize) -> Rc<MmapMut> {
     let file = OpenOptions::new()
         .read(true)
@@ -117,26 +149,99 @@ pub fn mmap_bcm_register(chip: PiChip, offset: u64, size_bytes: usize) -> Rc<Mma
     Rc::new(map)
 }
 
+/// Map the GPIO bank through `/dev/gpiomem`, which only exposes the GPIO registers, starting at offset 0, and
+/// is accessible to the `gpio` group.
+fn mmap_gpiomem() -> Option<MmapMut> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/gpiomem")
+        .ok()?;
+    unsafe { MmapOptions::new().len(GP_SIZE_BYTES).map_mut(&file) }.ok()
+}
+
+/// Map the GPIO registers through `/dev/gpiomem` if requested, or through `/dev/mem` otherwise. Returns `None`
+/// if `/dev/gpiomem` was requested but can't be mapped, instead of falling back to `/dev/mem`.
+fn mmap_gpio_registers(chip: PiChip, use_gpiomem: bool) -> Option<Rc<MmapMut>> {
+    if use_gpiomem {
+        mmap_gpiomem().map(Rc::new)
+    } else {
+        Some(mmap_bcm_register(chip, GP_OFFSET, GP_SIZE_BYTES))
+    }
+}
+
+/// Check that the GPIO registers can be mapped through `/dev/gpiomem`.
+pub(crate) fn gpiomem_accessible() -> bool {
+    mmap_gpiomem().is_some()
+}
+
+/// Check that the peripherals that are mapped through `/dev/mem` can actually be accessed. These are the system
+/// timer, the PWM and the clock registers, and the GPIO registers unless they are mapped through
+/// `/dev/gpiomem`. Newer kernels restrict the access even for root: opening succeeds, but mapping the
+/// peripherals fails or the mapped registers read as zero. The system timer is always running, so a restricted
+/// mapping shows up as a timer that does not advance.
+pub(crate) fn peripherals_accessible(chip: PiChip, use_gpiomem: bool) -> bool {
+    let Ok(file) = OpenOptions::new().read(true).write(true).open("/dev/mem") else {
+        return false;
+    };
+    let map = |offset: u64, size_bytes: usize| unsafe {
+        MmapOptions::new()
+            .offset(chip.get_peripherals_base() + offset)
+            .len(size_bytes)
+            .map_mut(&file)
+    };
+    let gpio_mapped = use_gpiomem || map(GP_OFFSET, GP_SIZE_BYTES).is_ok();
+    if !gpio_mapped
+        || map(PWM_OFFSET, PWM_SIZE_BYTES).is_err()
+        || map(CM_OFFSET, CM_SIZE_BYTES).is_err()
+    {
+        return false;
+    }
+    let Ok(map) = map(ST_OFFSET, ST_SIZE_BYTES) else {
+        return false;
+    };
+    let time = MmapPtr::<TimeRegister>::new(Rc::new(map), ST_CLO);
+    let start = time.read().get_u64();
+    sleep(Duration::from_micros(10));
+    time.read().get_u64() != start
+}
+
 pub(crate) struct GPIORegisters {
     clr0: MmapPtr<u32>,
     set0: MmapPtr<u32>,
     lvl0: MmapPtr<u32>,
     function_select: GPIOFunctionSelectRegisters,
+    pud: MmapPtr<u32>,
+    pud_clk0: MmapPtr<u32>,
+    pup_pdn_cntrl: [MmapPtr<u32>; 2],
+    /// The BCM2711 uses different registers to configure the pull resistors.
+    bcm2711_pulls: bool,
 }
 
 impl GPIORegisters {
-    pub(crate) fn new(chip: PiChip) -> Self {
-        let map = mmap_bcm_register(chip, GP_OFFSET, GP_SIZE_BYTES);
+    /// Map the GPIO registers, see [`mmap_gpio_registers`].
+    pub(crate) fn new(chip: PiChip, use_gpiomem: bool) -> Option<Self> {
+        let map = mmap_gpio_registers(chip, use_gpiomem)?;
         let clr0 = MmapPtr::new(map.clone(), GP_CLR0);
         let set0 = MmapPtr::new(map.clone(), GP_SET0);
         let lvl0 = MmapPtr::new(map.clone(), GP_LEV0);
+        let pud = MmapPtr::new(map.clone(), GP_PUD);
+        let pud_clk0 = MmapPtr::new(map.clone(), GP_PUDCLK0);
+        let pup_pdn_cntrl = [
+            MmapPtr::new(map.clone(), GP_PUP_PDN_CNTRL0),
+            MmapPtr::new(map.clone(), GP_PUP_PDN_CNTRL1),
+        ];
         let function_select = GPIOFunctionSelectRegisters::new(map, GP_FSEL0);
-        Self {
+        Some(Self {
             clr0,
             set0,
             lvl0,
             function_select,
-        }
+            pud,
+            pud_clk0,
+            pup_pdn_cntrl,
+            bcm2711_pulls: chip == PiChip::BCM2711,
+        })
     }
 
     pub(crate) fn write_clr_bits(&mut self, value: u32) {
@@ -154,6 +259,38 @@ impl GPIORegisters {
     pub(crate) fn read_pin_level0(&self) -> u32 {
         self.lvl0.read()
     }
+
+    /// Configure the pull resistors of the pins in `pins`.
+    pub(crate) fn set_pull(&mut self, pins: u32, pull: PullMode) {
+        if self.bcm2711_pulls {
+            let bits = match pull {
+                PullMode::Off => 0b00,
+                PullMode::Up => 0b01,
+                PullMode::Down => 0b10,
+            };
+            (0..32)
+                .filter(|pin| pins & (1 << pin) != 0)
+                .for_each(|pin| {
+                    let register = &self.pup_pdn_cntrl[pin / 16];
+                    let shift = (pin % 16) * 2;
+                    let value_before = register.read();
+                    register.write((value_before & !(0b11 << shift)) | (bits << shift));
+                });
+        } else {
+            // The control signal has to be set up and clocked in for at least 150 cycles each.
+            let bits = match pull {
+                PullMode::Off => 0b00,
+                PullMode::Down => 0b01,
+                PullMode::Up => 0b10,
+            };
+            self.pud.write(bits);
+            sleep(Duration::from_micros(5));
+            self.pud_clk0.write(pins);
+            sleep(Duration::from_micros(5));
+            self.pud.write(0);
+            self.pud_clk0.write(0);
+        }
+    }
 }
 
 // System Timer
@@ -161,8 +298,6 @@ const ST_OFFSET: u64 = 0x3000;
 const ST_SIZE_BYTES: usize = 28;
 const ST_CLO: usize = 0x4;
 
-const MIN_SYS_SLEEP_TIME_US: u64 = 100;
-
 /// Required to read `ST_CLO` and the adjacent `ST_CHI`.
 /// This has to be a struct so that we can have a fixed memory layout.
 #[repr(C)]
@@ -181,15 +316,18 @@ impl TimeRegister {
 pub(crate) struct TimeRegisters {
     time: MmapPtr<TimeRegister>,
     sleep_factor: f32,
+    /// Waits up to this duration are done by busy-waiting only.
+    min_sys_sleep_time_us: u64,
 }
 
 impl TimeRegisters {
-    pub(crate) fn new(chip: PiChip) -> Self {
+    pub(crate) fn new(chip: PiChip, min_sys_sleep_time_us: u64) -> Self {
         let map = mmap_bcm_register(chip, ST_OFFSET, ST_SIZE_BYTES);
         let time = MmapPtr::new(map, ST_CLO);
         Self {
             time,
             sleep_factor: 0.4,
+            min_sys_sleep_time_us,
         }
     }
 
@@ -206,7 +344,7 @@ impl TimeRegisters {
     }
 
     pub(crate) fn sleep_at_most(&mut self, duration_us: u64) {
-        if duration_us > MIN_SYS_SLEEP_TIME_US {
+        if duration_us > self.min_sys_sleep_time_us {
             let sys_sleep_time = (duration_us as f32 * self.sleep_factor) as u64;
             sleep(Duration::from_micros(sys_sleep_time));
         }
@@ -236,10 +374,12 @@ pub(crate) struct PWMRegisters {
     rng1: MmapPtr<u32>,
     fif1: MmapPtr<u32>,
     sta: MmapPtr<u32>,
+    /// The polarity bit. Set for the usual active-low output enable.
+    polarity: u32,
 }
 
 impl PWMRegisters {
-    pub(crate) fn new(chip: PiChip) -> Self {
+    pub(crate) fn new(chip: PiChip, oe_active_high: bool) -> Self {
         let map = mmap_bcm_register(chip, PWM_OFFSET, PWM_SIZE_BYTES);
         let ctl = MmapPtr::new(map.clone(), PWM_CTL);
         let rng1 = MmapPtr::new(map.clone(), PWM_RNG1);
@@ -250,17 +390,18 @@ impl PWMRegisters {
             rng1,
             fif1,
             sta,
+            polarity: if oe_active_high { 0 } else { PWM_CTL_POLA1 },
         }
     }
 
     /// Channel 1: Use FIFO | Polarity (1=low, 0=high) | Enable Channel
     pub(crate) fn enable_pwm(&mut self) {
-        self.set_pwm_ctl(PWM_CTL_USEF1 | PWM_CTL_POLA1 | PWM_CTL_PWEN1);
+        self.set_pwm_ctl(PWM_CTL_USEF1 | self.polarity | PWM_CTL_PWEN1);
     }
 
     /// Channel 1: Use FIFO | Polarity (1=low, 0=high) | Clear FIFO
     pub(crate) fn reset_pwm(&mut self) {
-        self.set_pwm_ctl(PWM_CTL_USEF1 | PWM_CTL_POLA1 | PWM_CTL_CLRF1);
+        self.set_pwm_ctl(PWM_CTL_USEF1 | self.polarity | PWM_CTL_CLRF1);
     }
 
     pub(crate) fn set_pwm_ctl(&mut self, value: u32) {
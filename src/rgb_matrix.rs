@@ -1,10 +1,16 @@
 use std::{
+    collections::HashMap,
     error::Error,
     fmt::{Display, Formatter},
     fs::{write, OpenOptions},
     mem::replace,
-    sync::mpsc::{
-        channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{
+            channel, sync_channel, Receiver, RecvTimeoutError, SendError, Sender, SyncSender,
+            TryRecvError, TrySendError,
+        },
+        Arc,
     },
     thread::{spawn, JoinHandle},
     time::Duration,
@@ -13,50 +19,105 @@ use std::{
 use thread_priority::{set_current_thread_priority, ThreadPriority};
 
 use crate::{
-    canvas::{Canvas, PixelDesignator, PixelDesignatorMap},
+    canvas::{Canvas, DisplaySettings, LedSequence, Overlay, PixelDesignator, PixelDesignatorMap},
     chip::PiChip,
+    color::{brightness_level, ChainGains},
+    config::{K_BIT_PLANES, MAX_PIXELS, SLOW_FRAMES_BEFORE_ADAPTING},
+    frame_sink::FrameSink,
+    frame_snapshot::{frame_snapshots, SnapshotReceiver},
     gpio::{Gpio, GpioInitializationError},
+    init_sequence::{run_init_sequence, ChainPanelTypes},
+    multiplex_mapper::MultiplexMapperType,
+    named_pixel_mapper::NamedPixelMapperType,
     pixel_mapper::{MultiplexMapperWrapper, NamedPixelMapperWrapper, PixelMapper},
+    registers::{gpiomem_accessible, peripherals_accessible},
+    row_address_setter::RowAddressSetterType,
+    terminal_preview::TerminalPreview,
     utils::{linux_has_isol_cpu, set_thread_affinity, FrameRateMonitor},
     RGBMatrixConfig,
 };
 
-fn initialize_update_thread(chip: PiChip) {
-    // Pin the thread to the last core to avoid the flicker resulting from context switching.
-    let last_core_id = chip.num_cores() - 1;
-    set_thread_affinity(last_core_id);
+/// A frame of row-major RGB bytes for [`RGBMatrix::run_frame_stream`].
+pub type FrameBytes = Vec<u8>;
+
+/// A best-effort setup step of the update thread that failed. The matrix still works, but the display might
+/// flicker or be less stable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitWarning {
+    /// The update thread could not be pinned to the core.
+    ThreadAffinity(usize),
+    /// The core is not isolated with `isolcpus`.
+    CoreNotIsolated(usize),
+    /// Realtime throttling could not be disabled.
+    RealtimeThrottling,
+    /// The core could not be set to performance mode.
+    PerformanceGovernor(usize),
+    /// The update thread could not get the highest priority.
+    ThreadPriority,
+}
+
+impl Display for InitWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitWarning::ThreadAffinity(core_id) => {
+                write!(f, "Could not pin the update thread to core {core_id}.")
+            }
+            InitWarning::CoreNotIsolated(core_id) => write!(
+                f,
+                "Suggestion: to slightly improve display update, add\n\tisolcpus={core_id}\nat \
+                the end of /boot/cmdline.txt and reboot"
+            ),
+            InitWarning::RealtimeThrottling => f.write_str("Could not disable realtime throttling"),
+            InitWarning::PerformanceGovernor(core_id) => {
+                write!(f, "Could not set core {core_id} to performance mode.")
+            }
+            InitWarning::ThreadPriority => f.write_str(
+                "Could not set thread priority. This might lead to reduced performance.",
+            ),
+        }
+    }
+}
+
+fn initialize_update_thread(chip: PiChip, core_id: usize, quiet: bool) -> Vec<InitWarning> {
+    let mut warnings = Vec::new();
+
+    // Pin the thread to a single core to avoid the flicker resulting from context switching.
+    if !set_thread_affinity(core_id) {
+        warnings.push(InitWarning::ThreadAffinity(core_id));
+    }
 
     // If the user has not setup isolcpus, let them know about the performance improvement.
-    if chip.num_cores() > 1 && !linux_has_isol_cpu(last_core_id) {
-        eprintln!(
-            "Suggestion: to slightly improve display update, add\n\tisolcpus={last_core_id}\nat \
-            the end of /boot/cmdline.txt and reboot"
-        );
+    if chip.num_cores() > 1 && !linux_has_isol_cpu(core_id) {
+        warnings.push(InitWarning::CoreNotIsolated(core_id));
     }
 
     // Disable realtime throttling.
     if chip.num_cores() > 1 && write("/proc/sys/kernel/sched_rt_runtime_us", "999000").is_err() {
-        eprintln!("Could not disable realtime throttling");
+        warnings.push(InitWarning::RealtimeThrottling);
     }
 
-    // Set the last core to performance mode.
+    // Set the core to performance mode.
     if chip.num_cores() > 1
         && write(
-            format!("/sys/devices/system/cpu/cpu{last_core_id}/cpufreq/scaling_governor"),
+            format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/scaling_governor"),
             "performance",
         )
         .is_err()
     {
-        eprintln!(
-            "Could not set core {} to performance mode.",
-            last_core_id + 1
-        );
+        warnings.push(InitWarning::PerformanceGovernor(core_id));
     }
 
     // Set the highest thread priority.
     if set_current_thread_priority(ThreadPriority::Max).is_err() {
-        eprintln!("Could not set thread priority. This might lead to reduced performance.",);
+        warnings.push(InitWarning::ThreadPriority);
+    }
+
+    if !quiet {
+        for warning in &warnings {
+            eprintln!("{warning}");
+        }
     }
+    warnings
 }
 
 #[derive(Debug)]
@@ -64,9 +125,22 @@ pub enum MatrixCreationError {
     ChipDeterminationError,
     TooManyParallelChains(usize),
     InvalidDitherBits(usize),
+    InvalidFirstBitPlane(usize, usize),
+    InvalidCpuCore(usize),
+    InvalidSubPanels(usize),
+    InvalidGeometry,
+    InvalidScan(usize),
+    MixedPanelTypes,
+    InvalidRowOrder(usize),
+    UnsupportedPanelSize(MultiplexMapperType, usize, usize),
+    InvalidCoordinateMap(usize, usize),
+    PixelMapperError(usize, String, String),
+    UnsupportedScan(usize, RowAddressSetterType),
     ThreadTimedOut,
     GpioError(GpioInitializationError),
     MemoryAccessError,
+    RestrictedMemoryAccess,
+    DryRun,
 }
 
 impl Error for MatrixCreationError {}
@@ -81,8 +155,62 @@ impl Display for MatrixCreationError {
                 write!(f, "GPIO mapping only supports up to {max} parallel panels.")
             }
             MatrixCreationError::InvalidDitherBits(value) => {
-                write!(f, "Unsupported dither bits '{value}'.")
+                let supported = RGBMatrixConfig::SUPPORTED_DITHER_BITS;
+                write!(
+                    f,
+                    "Unsupported dither bits '{value}', expected {} to {}.",
+                    supported.start(),
+                    supported.end()
+                )
             }
+            MatrixCreationError::InvalidFirstBitPlane(first, pwm_bits) => write!(
+                f,
+                "The first bit plane '{first}' leaves no room for {pwm_bits} PWM bits, expected at most {}.",
+                K_BIT_PLANES.saturating_sub(*pwm_bits)
+            ),
+            MatrixCreationError::InvalidCpuCore(core) => {
+                write!(f, "The CPU core '{core}' does not exist on this chip.")
+            }
+            MatrixCreationError::InvalidSubPanels(value) => write!(
+                f,
+                "Unsupported number of sub-panels '{value}'. The rows need to be divisible by it and each \
+                sub-panel can have at most 32 rows."
+            ),
+            MatrixCreationError::InvalidGeometry => write!(
+                f,
+                "Unsupported matrix size. The rows, columns, chain length and parallel chains need to be \
+                positive, and the matrix can have at most {MAX_PIXELS} pixels."
+            ),
+            MatrixCreationError::InvalidScan(scan) => write!(
+                f,
+                "A 1:{scan} scan does not match the configured rows, sub-panels and multiplexing. The \
+                multiplexing has to fold the rows of a sub-panel onto {scan} scanned rows."
+            ),
+            MatrixCreationError::MixedPanelTypes => {
+                f.write_str("FM6126 and FM6127 panels can't be mixed in one chain.")
+            }
+            MatrixCreationError::InvalidRowOrder(rows) => write!(
+                f,
+                "The row order has to contain each of the {rows} scanned rows, from 0 to {}, exactly once.",
+                rows.saturating_sub(1)
+            ),
+            MatrixCreationError::UnsupportedPanelSize(multiplexing, rows, cols) => write!(
+                f,
+                "The {multiplexing:?} multiplexing does not support panels with {rows} rows and {cols} columns. \
+                Many multiplexings work in blocks of 4 or 8 columns and some only support a single panel size."
+            ),
+            MatrixCreationError::InvalidCoordinateMap(width, height) => write!(
+                f,
+                "The coordinate map of the Custom pixel mapper maps to pixels outside of the {width} x {height} \
+                pixels it is applied to."
+            ),
+            MatrixCreationError::PixelMapperError(index, mapper, reason) => {
+                write!(f, "Pixel mapper {index} ({mapper}): {reason}")
+            }
+            MatrixCreationError::UnsupportedScan(scan, row_setter) => write!(
+                f,
+                "The {row_setter:?} row address setter can't select the {scan} rows of a 1:{scan} scan."
+            ),
             MatrixCreationError::ThreadTimedOut => {
                 f.write_str("The update thread did not return in time.")
             }
@@ -90,27 +218,121 @@ impl Display for MatrixCreationError {
                 write!(f, "GPIO initialization error: {error}")
             }
             MatrixCreationError::MemoryAccessError => f.write_str(
-                "Failed to access the physical memory. Not running with root privileges?",
+                "Failed to access the physical memory. Not running with root privileges? The PWM, clock and \
+                timer registers need '/dev/mem', also with --gpiomem.",
+            ),
+            MatrixCreationError::RestrictedMemoryAccess => f.write_str(
+                "'/dev/mem' could be opened, but the kernel restricts access to the peripherals. Add \
+                'iomem=relaxed' to the kernel command line (/boot/firmware/cmdline.txt) and reboot.",
             ),
+            MatrixCreationError::DryRun => {
+                f.write_str("Dry run requested, the hardware was not accessed.")
+            }
         }
     }
 }
 
+/// The update thread is no longer running, e.g. after [`RGBMatrix::take_last_canvas`] or because it panicked.
+/// Holds a canvas to keep using, usually the one that was passed in.
+pub struct UpdateThreadStopped {
+    pub canvas: Box<Canvas>,
+}
+
+impl std::fmt::Debug for UpdateThreadStopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdateThreadStopped")
+            .finish_non_exhaustive()
+    }
+}
+
+impl Error for UpdateThreadStopped {}
+
+impl Display for UpdateThreadStopped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("The display update thread is no longer running.")
+    }
+}
+
+/// Counters shared with the update thread to diagnose the canvas swap handshake and the output pulses.
+#[derive(Default)]
+struct FrameCounts {
+    /// Canvases received from [`RGBMatrix::update_on_vsync`].
+    submitted: AtomicU64,
+    /// Frames written to the matrix.
+    displayed: AtomicU64,
+    /// Output enable pulses whose end was not detected in time.
+    pulse_timeouts: AtomicU64,
+    /// The PWM bits the update thread shows at most, after adapting to [`RGBMatrixConfig::min_pwm_bits`].
+    adapted_pwm_bits: AtomicUsize,
+}
+
+/// Check that the peripherals of the chip can be accessed, the GPIO registers through `/dev/gpiomem` if
+/// requested and the others through `/dev/mem`.
+fn check_memory_access(chip: PiChip, use_gpiomem: bool) -> Result<(), MatrixCreationError> {
+    if use_gpiomem && !gpiomem_accessible() {
+        return Err(MatrixCreationError::GpioError(
+            GpioInitializationError::GpioMemUnavailable,
+        ));
+    }
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/mem")
+        .map_err(|_| MatrixCreationError::MemoryAccessError)?;
+    if !peripherals_accessible(chip, use_gpiomem) {
+        return Err(MatrixCreationError::RestrictedMemoryAccess);
+    }
+    Ok(())
+}
+
+/// A callback for [`RGBMatrix::on_frame_overrun`], called with the frame time in microseconds.
+pub type FrameOverrunCallback = Box<dyn Fn(u64) + Send>;
+
 pub struct RGBMatrix {
     /// The join handle of the update thread.
     thread_handle: Option<JoinHandle<()>>,
     /// Sender for the shutdown signal.
     shutdown_sender: Sender<()>,
+    /// Sender for switching the quality mode.
+    quality_mode_sender: Sender<bool>,
+    /// Sender for pausing and resuming the update thread.
+    pause_sender: Sender<bool>,
+    /// Sender for the callback registered with [`RGBMatrix::on_frame_overrun`].
+    frame_overrun_sender: Sender<(u64, FrameOverrunCallback)>,
+    /// Sender for the overlay set with [`RGBMatrix::set_overlay`].
+    overlay_sender: Sender<Option<Overlay>>,
     /// Receiver for GPIO inputs.
     input_receiver: Receiver<u32>,
     /// Channel to send canvas to update thread.
     canvas_to_thread_sender: SyncSender<Box<Canvas>>,
     /// Channel to receive canvas from update thread.
     canvas_from_thread_receiver: Receiver<Box<Canvas>>,
+    /// The canvas [`RGBMatrix::with_canvas`] draws into next, if it is not in use by the update thread.
+    spare_canvas: Option<Box<Canvas>>,
+    /// Receives the displayed canvas when the update thread exits.
+    last_canvas_receiver: Receiver<Box<Canvas>>,
+    /// A blank canvas to hand out if the update thread lost the canvas, e.g. by panicking.
+    blank_canvas: Box<Canvas>,
+    /// Display parameters set on the matrix, which are applied to every swapped canvas.
+    display_settings: DisplaySettings,
     /// Additional requested inputs that can be received.
     enabled_input_bits: u32,
+    /// Setup steps of the update thread that failed.
+    init_warnings: Vec<InitWarning>,
+    /// The most recently received GPIO inputs.
+    last_inputs: u32,
+    /// Inputs registered with [`RGBMatrix::define_input`].
+    named_inputs: HashMap<String, u32>,
+    /// Receiver for the snapshots requested with [`RGBMatrixConfig::preview_interval`].
+    preview_receiver: Option<SnapshotReceiver>,
+    /// The configuration with all defaults resolved.
+    effective_config: RGBMatrixConfig,
     /// Frame rate measurement.
     frame_rate_monitor: FrameRateMonitor,
+    /// Fraction of the last frame the update thread spent writing to the matrix, stored as `f32` bits.
+    thread_cpu_load: Arc<AtomicU32>,
+    /// Number of submitted and displayed frames.
+    frame_counts: Arc<FrameCounts>,
 }
 
 impl RGBMatrix {
@@ -121,73 +343,153 @@ impl RGBMatrix {
     /// [`RGBMatrix::receive_new_inputs`]. Only bits that are not already in use for reading or writing by the
     /// matrix are allowed. Use [`RGBMatrix::enabled_input_bits`] after calling this function to check which
     /// bits were actually available.
+    ///
+    /// Only one matrix can exist per process at a time. Creating a second one fails with a GPIO error. With
+    /// [`RGBMatrixConfig::dry_run`], this fails with [`MatrixCreationError::DryRun`] before touching the
+    /// hardware.
     pub fn new(
-        mut config: RGBMatrixConfig,
+        config: RGBMatrixConfig,
         requested_inputs: u32,
     ) -> Result<(Self, Box<Canvas>), MatrixCreationError> {
-        // Check if we can access the memory before doing anything else.
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open("/dev/mem")
-            .map_err(|_| MatrixCreationError::MemoryAccessError)?;
-
+        if config.dry_run {
+            return Err(MatrixCreationError::DryRun);
+        }
         let chip = if let Some(chip) = config.pi_chip {
             chip
         } else {
             PiChip::determine().ok_or(MatrixCreationError::ChipDeterminationError)?
         };
+        Self::new_with_chip(config, chip, requested_inputs)
+    }
 
-        let max_parallel = config.hardware_mapping.max_parallel_chains();
-        if config.parallel > max_parallel {
-            return Err(MatrixCreationError::TooManyParallelChains(max_parallel));
+    /// Like [`RGBMatrix::new`], but for a known chip. Neither `/proc/cpuinfo` nor [`RGBMatrixConfig::pi_chip`]
+    /// are consulted. Together with [`RGBMatrixConfig::skip_memory_check`], nothing is probed before the update
+    /// thread starts.
+    pub fn new_with_chip(
+        mut config: RGBMatrixConfig,
+        chip: PiChip,
+        requested_inputs: u32,
+    ) -> Result<(Self, Box<Canvas>), MatrixCreationError> {
+        if config.dry_run {
+            return Err(MatrixCreationError::DryRun);
         }
 
-        let pixel_designator = PixelDesignator::new(&config.hardware_mapping, config.led_sequence);
-        let width = config.cols * config.chain_length;
-        let height = config.rows * config.parallel;
-        let mut shared_mapper = PixelDesignatorMap::new(pixel_designator, width, height, &config);
+        if let Some(profile) = config.panel_profile {
+            profile.apply(&mut config);
+        }
 
-        // Apply the mapping for the panels first.
-        if let Some(mapper_type) = config.multiplexing.as_ref() {
-            let mut mapper = mapper_type.create();
-            mapper.edit_rows_cols(&mut config.rows, &mut config.cols);
-            let mapper = MultiplexMapperWrapper(mapper);
-            shared_mapper =
-                Self::apply_pixel_mapper(&shared_mapper, &mapper, &config, pixel_designator);
+        if let Some(e_pin) = config.e_pin {
+            config.hardware_mapping = config.hardware_mapping.with_e_pin(e_pin);
         }
 
-        // Apply higher level mappers that might arrange panels.
-        let pixelmappers = config.pixelmapper.clone();
-        for mapper_type in pixelmappers {
-            let mapper: NamedPixelMapperWrapper =
-                NamedPixelMapperWrapper(mapper_type.create(config.chain_length, config.parallel));
-            shared_mapper =
-                Self::apply_pixel_mapper(&shared_mapper, &mapper, &config, pixel_designator);
+        // Check if we can access the memory before doing anything else.
+        if !config.skip_memory_check {
+            check_memory_access(chip, config.gpiomem)?;
         }
 
-        let dither_start_bits = match config.dither_bits {
-            0 => [0, 0, 0, 0],
-            1 => [0, 1, 0, 1],
-            2 => [0, 1, 2, 2],
-            _ => return Err(MatrixCreationError::InvalidDitherBits(config.dither_bits)),
-        };
+        // Pin the update thread to the last core unless requested otherwise.
+        let core_id = config.cpu_core.unwrap_or(chip.num_cores() - 1);
+        if core_id >= chip.num_cores() {
+            return Err(MatrixCreationError::InvalidCpuCore(core_id));
+        }
+
+        if config.checked_pixels().is_none() {
+            return Err(MatrixCreationError::InvalidGeometry);
+        }
+
+        if !config.valid_sub_panels() {
+            return Err(MatrixCreationError::InvalidSubPanels(config.sub_panels));
+        }
+
+        if let Some(scan) = config.scan {
+            config.multiplexing = config.scan_multiplexing(scan)?;
+        }
+
+        if let Some(multiplexing) = config.multiplexing {
+            if !multiplexing.supports_panel(config.rows, config.cols) {
+                return Err(MatrixCreationError::UnsupportedPanelSize(
+                    multiplexing,
+                    config.rows,
+                    config.cols,
+                ));
+            }
+        }
+
+        let max_parallel = config.hardware_mapping.max_parallel_chains();
+        if config.used_chains() > max_parallel {
+            return Err(MatrixCreationError::TooManyParallelChains(
+                max_parallel / config.chains_per_panel(),
+            ));
+        }
+
+        if config
+            .panel_types
+            .as_ref()
+            .is_some_and(ChainPanelTypes::is_mixed)
+        {
+            return Err(MatrixCreationError::MixedPanelTypes);
+        }
+
+        // Resolve the defaults that depend on the chip.
+        config.pi_chip = Some(chip);
+        config.slowdown = Some(config.slowdown.unwrap_or_else(|| chip.gpio_slowdown()));
+        config.cpu_core = Some(core_id);
+        config.quality_pwm_lsb_nanoseconds = Some(config.quality_pwm_lsb_nanoseconds());
+        let effective_config = config.clone();
+
+        // Remember the panel size before the multiplexing mapper alters it.
+        let panel_size = [config.rows, config.cols];
+
+        let shared_mapper = Self::create_shared_mapper(&mut config)?;
+
+        // The rows of a custom order are the rows scanned by the multiplexed panels.
+        if config.row_order().rows(config.double_rows()).is_none() {
+            return Err(MatrixCreationError::InvalidRowOrder(config.double_rows()));
+        }
+
+        let dither_start_bits = config
+            .dither_start_bits()
+            .ok_or(MatrixCreationError::InvalidDitherBits(config.dither_bits))?;
+        if config.bit_plane_shift().is_none() {
+            return Err(MatrixCreationError::InvalidFirstBitPlane(
+                config.first_bit_plane.unwrap_or_default(),
+                config.pwm_bits,
+            ));
+        }
 
         // Create two canvases, one for the display update thread and one for the user to modify. They will be
         // swapped out after each frame.
-        let canvas = Box::new(Canvas::new(&config, shared_mapper));
+        let canvas = Box::new(Canvas::new(&config, shared_mapper, panel_size));
         let mut thread_canvas = canvas.clone();
 
-        let (canvas_to_thread_sender, canvas_to_thread_receiver) = sync_channel::<Box<Canvas>>(0);
+        // A single canvas can be queued so that `with_canvas` doesn't have to wait for the end of the frame.
+        let (canvas_to_thread_sender, canvas_to_thread_receiver) = sync_channel::<Box<Canvas>>(1);
         let (canvas_from_thread_sender, canvas_from_thread_receiver) =
             sync_channel::<Box<Canvas>>(1);
+        let (last_canvas_sender, last_canvas_receiver) = channel::<Box<Canvas>>();
         let (shutdown_sender, shutdown_receiver) = channel::<()>();
-        let (input_sender, input_receiver) = channel::<u32>();
+        let (quality_mode_sender, quality_mode_receiver) = channel::<bool>();
+        let (pause_sender, pause_receiver) = channel::<bool>();
+        let (frame_overrun_sender, frame_overrun_receiver) =
+            channel::<(u64, FrameOverrunCallback)>();
+        let (overlay_sender, overlay_receiver) = channel::<Option<Overlay>>();
+        let (input_sender, input_receiver) = sync_channel::<u32>(config.input_queue_size.max(1));
+        let preview_interval = config.preview_interval.filter(|&interval| interval > 0);
+        let (preview_sender, preview_receiver) =
+            preview_interval.map(|_| frame_snapshots(&canvas)).unzip();
         let (thread_start_result_sender, thread_start_result_receiver) =
-            channel::<Result<u32, MatrixCreationError>>();
+            channel::<Result<(u32, Vec<InitWarning>), MatrixCreationError>>();
+        let thread_cpu_load = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+        let thread_cpu_load_writer = thread_cpu_load.clone();
+        let frame_counts = Arc::new(FrameCounts::default());
+        frame_counts
+            .adapted_pwm_bits
+            .store(config.pwm_bits, Ordering::Relaxed);
+        let framerate_window = config.framerate_window;
+        let frame_counts_writer = frame_counts.clone();
 
         let thread_handle = spawn(move || {
-            initialize_update_thread(chip);
+            let init_warnings = initialize_update_thread(chip, core_id, config.quiet);
 
             let mut address_setter = config.row_setter.create(&config);
 
@@ -202,7 +504,10 @@ impl RGBMatrix {
             };
 
             // Run the initialization sequence if necessary.
-            if let Some(panel_type) = config.panel_type {
+            if let Some(panel_types) = config.panel_types.as_ref() {
+                let panel_types = panel_types.for_chain(config.chain_length);
+                run_init_sequence(&mut gpio, &config, &panel_types);
+            } else if let Some(panel_type) = config.panel_type {
                 panel_type.run_init_sequence(&mut gpio, &config);
             }
 
@@ -213,16 +518,61 @@ impl RGBMatrix {
 
             let frame_time_target_us = (1_000_000.0 / config.refresh_rate as f64) as u64;
 
-            let color_clk_mask = config
-                .hardware_mapping
-                .get_color_clock_mask(config.parallel);
+            // Bit planes below this are not shown after lowering the PWM bits to reach the refresh rate.
+            let min_pwm_bits = config
+                .min_pwm_bits
+                .map(|min_pwm_bits| min_pwm_bits.max(1).min(config.pwm_bits));
+            let mut adapted_low_bit = K_BIT_PLANES.saturating_sub(config.pwm_bits);
+            let mut slow_frames = 0;
+
+            let color_clk_mask = config.color_clock_mask.unwrap_or_else(|| {
+                config
+                    .hardware_mapping
+                    .get_color_clock_mask(config.used_chains())
+            });
+
+            let frame_sink = config
+                .frame_sink
+                .clone()
+                .map(|path| FrameSink::new(path, &thread_canvas));
+
+            let mut frames_until_preview = 0;
+
+            let terminal_preview = config.terminal_preview_ms.map(|interval_ms| {
+                let preview = TerminalPreview::new(&thread_canvas);
+                (preview, interval_ms.saturating_mul(1000))
+            });
+            let mut next_terminal_preview_time = 0;
+
+            let mut frame_overrun: Option<(u64, FrameOverrunCallback)> = None;
 
-            let enabled_input_bits = gpio.request_enabled_inputs(requested_inputs);
+            let mut overlay: Option<Overlay> = None;
+            // The content of the canvas without the overlay while it is shown.
+            let mut overlay_backup = Vec::new();
+
+            let enabled_input_bits = match config.input_pull {
+                Some(pull) => gpio.request_enabled_inputs_with_pull(requested_inputs, pull),
+                None => gpio.request_enabled_inputs(requested_inputs),
+            };
             thread_start_result_sender
-                .send(Ok(enabled_input_bits))
+                .send(Ok((enabled_input_bits, init_warnings)))
                 .expect("Could not send to main thread.");
 
             'thread: loop {
+                // Blank the display and sleep until resumed. Shutting down resumes as well.
+                if pause_receiver.try_iter().last() == Some(true) {
+                    let mut blank_canvas = thread_canvas.clone();
+                    blank_canvas.blank();
+                    blank_canvas.dump_to_matrix(
+                        &mut gpio,
+                        &config.hardware_mapping,
+                        address_setter.as_mut(),
+                        0,
+                        color_clk_mask,
+                    );
+                    while pause_receiver.recv() == Ok(true) {}
+                }
+
                 let start_time = gpio.get_time();
                 loop {
                     // Try to receive a shutdown request.
@@ -232,18 +582,23 @@ impl RGBMatrix {
                     // Read input bits and send them if they have changed.
                     let new_inputs = gpio.read();
                     if new_inputs != last_gpio_inputs {
-                        match input_sender.send(new_inputs) {
-                            Ok(()) => {}
-                            Err(_) => {
+                        match input_sender.try_send(new_inputs) {
+                            Ok(()) => last_gpio_inputs = new_inputs,
+                            // Nobody is receiving the inputs. Try again later so that the most recent state
+                            // is sent once there is space.
+                            Err(TrySendError::Full(_)) => {}
+                            Err(TrySendError::Disconnected(_)) => {
                                 break 'thread;
                             }
                         }
-                        last_gpio_inputs = new_inputs;
                     }
                     // Wait for a swap canvas.
                     match canvas_to_thread_receiver.recv_timeout(Duration::from_millis(1)) {
                         Ok(new_canvas) => {
                             let old_canvas = replace(&mut thread_canvas, new_canvas);
+                            frame_counts_writer
+                                .submitted
+                                .fetch_add(1, Ordering::Relaxed);
                             match canvas_from_thread_sender.send(old_canvas) {
                                 Ok(()) => break,
                                 Err(_) => {
@@ -254,18 +609,72 @@ impl RGBMatrix {
                         Err(RecvTimeoutError::Disconnected) => {
                             break 'thread;
                         }
-                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Timeout) => {
+                            // Show the current canvas again rather than going dark if no new one arrives
+                            // within a frame, e.g. with `with_canvas` at a low frame rate.
+                            if gpio.get_time() - start_time >= frame_time_target_us {
+                                break;
+                            }
+                        }
                     }
                 }
 
+                // Switch the timings between frames only.
+                if let Some(quality_mode) = quality_mode_receiver.try_iter().last() {
+                    gpio.set_quality_mode(quality_mode);
+                }
+                if let Some(new_frame_overrun) = frame_overrun_receiver.try_iter().last() {
+                    frame_overrun = Some(new_frame_overrun);
+                }
+                if let Some(new_overlay) = overlay_receiver.try_iter().last() {
+                    overlay = new_overlay;
+                }
+                if let Some(overlay) = overlay.as_ref() {
+                    thread_canvas.apply_overlay(overlay, &mut overlay_backup);
+                }
+
+                let dump_start_time = gpio.get_time();
                 thread_canvas.dump_to_matrix(
                     &mut gpio,
                     &config.hardware_mapping,
                     address_setter.as_mut(),
-                    dither_start_bits[dither_low_bit_sequence % dither_start_bits.len()],
+                    dither_start_bits[dither_low_bit_sequence % dither_start_bits.len()]
+                        .max(adapted_low_bit),
                     color_clk_mask,
                 );
+                let busy_time = gpio.get_time() - dump_start_time;
                 dither_low_bit_sequence += 1;
+                frame_counts_writer
+                    .displayed
+                    .fetch_add(1, Ordering::Relaxed);
+
+                if let Some(frame_sink) = frame_sink.as_ref() {
+                    frame_sink.push(&thread_canvas);
+                }
+
+                if let (Some(preview_interval), Some(preview_sender)) =
+                    (preview_interval, preview_sender.as_ref())
+                {
+                    if frames_until_preview == 0 {
+                        frames_until_preview = preview_interval;
+                        // Drop the snapshot if the previous one was not received yet.
+                        preview_sender.push(&thread_canvas);
+                    }
+                    frames_until_preview -= 1;
+                }
+
+                if let Some((terminal_preview, interval_us)) = terminal_preview.as_ref() {
+                    let now_time = gpio.get_time();
+                    if now_time >= next_terminal_preview_time {
+                        next_terminal_preview_time = now_time + interval_us;
+                        terminal_preview.push(&thread_canvas);
+                    }
+                }
+
+                // Hand back the canvas as it was drawn.
+                if overlay.is_some() {
+                    thread_canvas.remove_overlay(&mut overlay_backup);
+                }
 
                 // Sleep for the rest of the frame.
                 let now_time = gpio.get_time();
@@ -273,20 +682,61 @@ impl RGBMatrix {
                 if let Some(remaining_time) = end_time.checked_sub(now_time) {
                     gpio.sleep(remaining_time);
                 }
+                if let Some((threshold_us, callback)) = frame_overrun.as_ref() {
+                    if now_time > end_time + threshold_us {
+                        callback(now_time - start_time);
+                    }
+                }
+
+                // Report how much of the frame was spent writing to the matrix.
+                let frame_time = gpio.get_time() - start_time;
+                let load = busy_time as f32 / frame_time.max(1) as f32;
+                thread_cpu_load_writer.store(load.to_bits(), Ordering::Relaxed);
+                frame_counts_writer
+                    .pulse_timeouts
+                    .store(gpio.pulse_timeouts(), Ordering::Relaxed);
+
+                // Drop the lowest shown bit plane if writing the frames keeps taking longer than a frame.
+                if let Some(min_pwm_bits) = min_pwm_bits {
+                    slow_frames = if busy_time > frame_time_target_us {
+                        slow_frames + 1
+                    } else {
+                        0
+                    };
+                    let pwm_bits = K_BIT_PLANES - adapted_low_bit;
+                    if slow_frames >= SLOW_FRAMES_BEFORE_ADAPTING && pwm_bits > min_pwm_bits {
+                        slow_frames = 0;
+                        adapted_low_bit += 1;
+                        frame_counts_writer
+                            .adapted_pwm_bits
+                            .store(pwm_bits - 1, Ordering::Relaxed);
+                        if !config.quiet {
+                            eprintln!(
+                                "Lowered the PWM bits to {} to reach the refresh rate of {} Hz.",
+                                pwm_bits - 1,
+                                config.refresh_rate
+                            );
+                        }
+                    }
+                }
             }
 
-            // Turn it off.
-            thread_canvas.fill(0, 0, 0);
-            thread_canvas.dump_to_matrix(
-                &mut gpio,
-                &config.hardware_mapping,
-                address_setter.as_mut(),
-                0,
-                color_clk_mask,
-            );
+            // Turn it off, but hand back the content for `take_last_canvas`.
+            let last_canvas = thread_canvas.clone();
+            if config.blank_on_drop {
+                thread_canvas.blank();
+                thread_canvas.dump_to_matrix(
+                    &mut gpio,
+                    &config.hardware_mapping,
+                    address_setter.as_mut(),
+                    0,
+                    color_clk_mask,
+                );
+            }
+            last_canvas_sender.send(last_canvas).ok();
         });
 
-        let enabled_input_bits = thread_start_result_receiver
+        let (enabled_input_bits, init_warnings) = thread_start_result_receiver
             .recv_timeout(Duration::from_secs(10))
             .map_err(|_| MatrixCreationError::ThreadTimedOut)??;
 
@@ -294,58 +744,334 @@ impl RGBMatrix {
             thread_handle: Some(thread_handle),
             input_receiver,
             shutdown_sender,
+            quality_mode_sender,
+            pause_sender,
+            frame_overrun_sender,
+            overlay_sender,
             canvas_to_thread_sender,
             canvas_from_thread_receiver,
+            spare_canvas: Some(canvas.clone()),
+            last_canvas_receiver,
+            blank_canvas: canvas.clone(),
+            display_settings: DisplaySettings::default(),
             enabled_input_bits,
-            frame_rate_monitor: FrameRateMonitor::new(),
+            last_inputs: 0,
+            init_warnings,
+            named_inputs: HashMap::new(),
+            preview_receiver,
+            effective_config,
+            frame_rate_monitor: FrameRateMonitor::new(framerate_window),
+            thread_cpu_load,
+            frame_counts,
         };
 
         Ok((rgbmatrix, canvas))
     }
 
+    /// Map the visible pixels to the matrix. The multiplexing mapper changes the rows and columns of `config` to
+    /// the physical layout of a panel, which the matrix is built with, before its mapping is applied. The
+    /// pixel scale is applied last, to the pixels arranged by the pixel mappers. Fails if a mapper can't be
+    /// created or doesn't fit the pixels of the previous stage, naming the mapper by its position in the list.
+    pub(crate) fn create_shared_mapper(
+        config: &mut RGBMatrixConfig,
+    ) -> Result<PixelDesignatorMap, MatrixCreationError> {
+        let [panel_rows, panel_cols] = [config.rows, config.cols];
+        let multiplex_mapper = config.multiplexing.map(|mapper_type| {
+            let mut mapper = mapper_type.create();
+            mapper.edit_rows_cols(&mut config.rows, &mut config.cols);
+            (mapper_type, MultiplexMapperWrapper(mapper))
+        });
+
+        let pixel_designator = PixelDesignator::new(&config.hardware_mapping, config.led_sequence);
+        let width = config.cols * config.chain_length;
+        let height = config.rows * config.parallel;
+        let mut shared_mapper = PixelDesignatorMap::new(pixel_designator, width, height, config);
+
+        // Apply the mapping for the panels first.
+        if let Some((mapper_type, mapper)) = multiplex_mapper {
+            shared_mapper = Self::apply_pixel_mapper(&shared_mapper, &mapper).map_err(|_| {
+                MatrixCreationError::UnsupportedPanelSize(mapper_type, panel_rows, panel_cols)
+            })?;
+        }
+
+        // Apply higher level mappers that might arrange panels.
+        let pixelmappers = config.pixelmapper.clone();
+        for (index, mapper_type) in pixelmappers.into_iter().enumerate() {
+            if let NamedPixelMapperType::Custom(map) = &mapper_type {
+                if !map.fits(shared_mapper.width(), shared_mapper.height()) {
+                    return Err(MatrixCreationError::InvalidCoordinateMap(
+                        shared_mapper.width(),
+                        shared_mapper.height(),
+                    ));
+                }
+            }
+            let name = format!("{mapper_type:?}");
+            let pixel_mapper_error =
+                |reason| MatrixCreationError::PixelMapperError(index + 1, name.clone(), reason);
+            let mapper = mapper_type
+                .create(config.chain_length, config.parallel)
+                .map_err(pixel_mapper_error)?;
+            shared_mapper =
+                Self::apply_pixel_mapper(&shared_mapper, &NamedPixelMapperWrapper(mapper))
+                    .map_err(pixel_mapper_error)?;
+        }
+        Ok(shared_mapper.upscaled(config.pixel_scale))
+    }
+
+    /// Map the pixels of the previous stage with a mapper. Fails with the reason if the mapper leaves no
+    /// visible pixels or maps a visible pixel outside of the previous stage.
     fn apply_pixel_mapper(
         shared_mapper: &PixelDesignatorMap,
         mapper: &impl PixelMapper,
-        config: &RGBMatrixConfig,
-        pixel_designator: PixelDesignator,
-    ) -> PixelDesignatorMap {
+    ) -> Result<PixelDesignatorMap, String> {
         let old_width = shared_mapper.width();
         let old_height = shared_mapper.height();
         let [new_width, new_height] = mapper.get_size_mapping(old_width, old_height);
-        let mut new_mapper =
-            PixelDesignatorMap::new(pixel_designator, new_width, new_height, config);
+        if new_width == 0 || new_height == 0 {
+            return Err(format!(
+                "maps the {old_width} x {old_height} pixels of the previous stage to {new_width} x \
+                 {new_height} visible pixels."
+            ));
+        }
+        let mut new_mapper = shared_mapper.resized(new_width, new_height);
+        // Keep track of which pixels of the previous mapping are still visible.
+        let mut visited = vec![false; old_width * old_height];
+        let mut one_to_one = shared_mapper.is_one_to_one();
         for y in 0..new_height {
             for x in 0..new_width {
+                if !mapper.is_visible(x, y) {
+                    new_mapper.get_mut(x, y).unwrap().set_unused();
+                    one_to_one = false;
+                    continue;
+                }
                 let [orig_x, orig_y] = mapper.map_visible_to_matrix(old_width, old_height, x, y);
                 if orig_x >= old_width || orig_y >= old_height {
-                    eprintln!("Error in pixel mapper"); // TODO
-                    continue;
+                    return Err(format!(
+                        "maps the visible pixel ({x}, {y}) of {new_width} x {new_height} to \
+                         ({orig_x}, {orig_y}), outside of the {old_width} x {old_height} pixels of the \
+                         previous stage."
+                    ));
                 }
                 let orig_designator = shared_mapper.get(orig_x, orig_y).unwrap();
                 *new_mapper.get_mut(x, y).unwrap() = *orig_designator;
+                one_to_one &= !replace(&mut visited[orig_y * old_width + orig_x], true);
             }
         }
-        new_mapper
+        one_to_one &= visited.iter().all(|v| *v);
+        new_mapper.set_one_to_one(one_to_one);
+        Ok(new_mapper)
     }
 
     /// Updates the matrix with the new canvas. Blocks until the end of the current frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the update thread is no longer running. Use [`RGBMatrix::try_update_on_vsync`] to handle this.
     pub fn update_on_vsync(&mut self, canvas: Box<Canvas>) -> Box<Canvas> {
-        let Self {
-            canvas_to_thread_sender,
-            canvas_from_thread_receiver,
-            frame_rate_monitor,
-            ..
-        } = self;
+        self.try_update_on_vsync(canvas)
+            .expect("Display update thread shut down unexpectedly.")
+    }
+
+    /// Like [`RGBMatrix::update_on_vsync`], but returns an error holding a canvas to keep using instead of
+    /// panicking if the update thread is no longer running. This is the case after
+    /// [`RGBMatrix::take_last_canvas`].
+    pub fn try_update_on_vsync(
+        &mut self,
+        mut canvas: Box<Canvas>,
+    ) -> Result<Box<Canvas>, UpdateThreadStopped> {
+        canvas.apply_display_settings(&self.display_settings);
+        if let Err(SendError(canvas)) = self.canvas_to_thread_sender.send(canvas) {
+            return Err(UpdateThreadStopped { canvas });
+        }
+
+        self.frame_rate_monitor.update();
+
+        match self.canvas_from_thread_receiver.recv() {
+            Ok(mut canvas) => {
+                canvas.apply_display_settings(&self.display_settings);
+                Ok(canvas)
+            }
+            Err(_) => Err(UpdateThreadStopped {
+                canvas: self.reclaim_canvas(),
+            }),
+        }
+    }
+
+    /// Stop the update thread and return the canvas it displayed last, e.g. to save it during an orderly
+    /// shutdown. The display is turned off unless `blank_on_drop` is disabled. Afterwards,
+    /// [`RGBMatrix::try_update_on_vsync`] returns an error and [`RGBMatrix::update_on_vsync`] panics. Calling
+    /// this again returns a blank canvas.
+    pub fn take_last_canvas(&mut self) -> Box<Canvas> {
+        self.stop_thread();
+        self.reclaim_canvas()
+    }
+
+    /// The canvas the update thread handed back when exiting, or a blank one if it was lost or already taken.
+    fn reclaim_canvas(&mut self) -> Box<Canvas> {
+        self.stop_thread();
+        self.last_canvas_receiver
+            .try_recv()
+            .unwrap_or_else(|_| self.blank_canvas.clone())
+    }
+
+    /// Request the update thread to stop and wait for it to exit.
+    fn stop_thread(&mut self) {
+        if let Some(handle) = self.thread_handle.take() {
+            self.shutdown_sender.send(()).ok();
+            self.pause_sender.send(false).ok();
+            let _result = handle.join();
+        }
+    }
 
-        canvas_to_thread_sender
+    /// Draw the next frame into a canvas owned by the matrix and submit it, without handing canvases back and
+    /// forth as with [`RGBMatrix::update_on_vsync`]. Doesn't wait for the end of the frame, unless the previously
+    /// submitted canvas is still waiting for it, i.e. when called faster than the refresh rate. The canvas
+    /// contains an earlier frame, so clear it first if needed. The last submitted canvas stays on the display
+    /// until the next one. Don't mix this with [`RGBMatrix::update_on_vsync`].
+    pub fn with_canvas(&mut self, draw: impl FnOnce(&mut Canvas)) {
+        // Only the first call uses the spare canvas. Afterwards, recycle the canvas the update thread returns once
+        // it takes the previously submitted one.
+        let mut canvas = match self.spare_canvas.take() {
+            Some(canvas) => canvas,
+            None => self
+                .canvas_from_thread_receiver
+                .recv()
+                .expect("Display update thread shut down unexpectedly."),
+        };
+
+        canvas.apply_display_settings(&self.display_settings);
+        draw(&mut canvas);
+
+        self.canvas_to_thread_sender
             .send(canvas)
             .expect("Display update thread shut down unexpectedly.");
 
-        frame_rate_monitor.update();
+        self.frame_rate_monitor.update();
+    }
 
-        canvas_from_thread_receiver
-            .recv()
-            .expect("Display update thread shut down unexpectedly.")
+    /// Display a stream of frames, e.g. decoded video received over the network, until the sender disconnects.
+    /// Each frame is written to the canvas with [`Canvas::set_rgb_bytes`] and shown on the next vsync. Frames that
+    /// arrive while the previous one is still waiting for its vsync are skipped, so the display never falls
+    /// behind the stream. Run this on a dedicated thread to decouple the display from the source of the frames.
+    /// Returns the canvas for further use.
+    pub fn run_frame_stream(
+        &mut self,
+        mut canvas: Box<Canvas>,
+        frames: Receiver<FrameBytes>,
+    ) -> Box<Canvas> {
+        while let Ok(frame) = frames.recv() {
+            let frame = frames.try_iter().last().unwrap_or(frame);
+            canvas.set_rgb_bytes(&frame);
+            canvas = self.update_on_vsync(canvas);
+        }
+        canvas
+    }
+
+    /// Switch to the longer on-times of [`RGBMatrixConfig::quality_pwm_lsb_nanoseconds`], or back. This trades
+    /// refresh rate for more detail in dark colors, e.g. while showing a still image. The switch happens
+    /// between frames.
+    pub fn set_quality_mode(&self, enabled: bool) {
+        self.quality_mode_sender.send(enabled).ok();
+    }
+
+    /// Blank the display and let the update thread sleep until [`RGBMatrix::resume`], e.g. to save power between
+    /// scheduled shows. While paused, no frames are shown and no inputs are read, and
+    /// [`RGBMatrix::update_on_vsync`] blocks until the thread is resumed.
+    pub fn pause(&self) {
+        self.pause_sender.send(true).ok();
+    }
+
+    /// Continue refreshing the display after [`RGBMatrix::pause`].
+    pub fn resume(&self) {
+        self.pause_sender.send(false).ok();
+    }
+
+    /// Draw `overlay` over every frame, e.g. a clock over changing content, without drawing it into each canvas.
+    /// Black pixels of the overlay are transparent. The overlay is copied, so later changes to it only show after
+    /// calling this again. `None` removes the overlay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the overlay is not a canvas of this matrix, e.g. a clone of the canvas returned by
+    /// [`RGBMatrix::new`].
+    pub fn set_overlay(&self, overlay: Option<Box<Canvas>>) {
+        let overlay = overlay.map(|canvas| {
+            let overlay = Overlay::new(&canvas);
+            assert!(
+                overlay.fits(&self.blank_canvas),
+                "The overlay needs to be a canvas of this matrix."
+            );
+            overlay
+        });
+        self.overlay_sender.send(overlay).ok();
+    }
+
+    /// Register a callback that is called with the frame time in microseconds whenever a frame takes longer
+    /// than the frame time of [`RGBMatrixConfig::refresh_rate`] by more than `threshold_us`, e.g. to log stalls
+    /// as they happen. The callback runs on the update thread, so it should return quickly. Registering a new
+    /// callback replaces the previous one.
+    pub fn on_frame_overrun(&self, threshold_us: u64, callback: FrameOverrunCallback) {
+        self.frame_overrun_sender
+            .send((threshold_us, callback))
+            .ok();
+    }
+
+    /// Set the brightness in percent of the canvases handed to and from the matrix, instead of setting it on
+    /// each canvas. Like [`Canvas::set_brightness`], this applies to the pixels drawn afterwards, so the display
+    /// follows with the next frame that is drawn from scratch.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.set_brightness_level(brightness_level(brightness.max(1)));
+    }
+
+    /// Set the brightness in 256 levels, from 0 (off) to 255 (full brightness), of the canvases handed to and from
+    /// the matrix. See [`RGBMatrix::set_brightness`].
+    pub fn set_brightness_level(&mut self, level: u8) {
+        self.display_settings.brightness = Some(level);
+    }
+
+    /// Get the brightness level of the canvases handed out by the matrix, from 0 (off) to 255.
+    #[must_use]
+    pub fn brightness_level(&self) -> u8 {
+        self.display_settings
+            .brightness
+            .unwrap_or_else(|| self.blank_canvas.brightness_level())
+    }
+
+    /// Set the PWM bits of the canvases handed to and from the matrix, between 1 and 11. The bit planes below
+    /// the new PWM bits are cleared, see [`Canvas::set_pwm_bits_and_clear`].
+    pub fn set_pwm_bits(&mut self, pwm_bits: usize) {
+        self.display_settings.pwm_bits = Some(pwm_bits.clamp(1, K_BIT_PLANES));
+    }
+
+    /// Get the PWM bits of the canvases handed out by the matrix. See also [`RGBMatrix::adapted_pwm_bits`].
+    #[must_use]
+    pub fn pwm_bits(&self) -> usize {
+        self.display_settings
+            .pwm_bits
+            .unwrap_or_else(|| self.blank_canvas.pwm_bits())
+    }
+
+    /// Set the brightness of each panel along the chain of the canvases handed to and from the matrix, like
+    /// [`RGBMatrixConfig::chain_gains`].
+    pub fn set_chain_gains(&mut self, chain_gains: ChainGains) {
+        self.display_settings.chain_gains = Some(chain_gains.0);
+    }
+
+    /// Get the brightness of each panel along the chain of the canvases handed out by the matrix.
+    #[must_use]
+    pub fn chain_gains(&self) -> ChainGains {
+        ChainGains(
+            self.display_settings
+                .chain_gains
+                .clone()
+                .unwrap_or_else(|| self.blank_canvas.chain_gains().to_vec()),
+        )
+    }
+
+    /// Change the LED sequence of the canvases handed to and from the matrix, e.g. to find the sequence of
+    /// unknown panels interactively. See [`Canvas::set_led_sequence`].
+    pub fn set_led_sequence(&mut self, sequence: LedSequence) {
+        self.display_settings.led_sequence = Some(sequence);
     }
 
     /// Get the bits that were available for input.
@@ -356,26 +1082,284 @@ impl RGBMatrix {
 
     /// Tries to receive a new GPIO input as specified with [`RGBMatrix::request_enabled_inputs`].
     pub fn receive_new_inputs(&mut self, timeout: Duration) -> Option<u32> {
-        self.input_receiver.recv_timeout(timeout).ok()
+        let inputs = self.input_receiver.recv_timeout(timeout).ok()?;
+        self.last_inputs = inputs;
+        Some(inputs)
+    }
+
+    /// Consumes all pending GPIO inputs and returns only the most recent one, if any. Use this to react to the
+    /// current input state instead of replaying every change.
+    pub fn drain_inputs(&mut self) -> Option<u32> {
+        let inputs = self.input_receiver.try_iter().last()?;
+        self.last_inputs = inputs;
+        Some(inputs)
+    }
+
+    /// Give a name to one or more input bits, e.g. `matrix.define_input("up", gpio_bits!(5))`. Only bits in
+    /// [`RGBMatrix::enabled_input_bits`] are ever read.
+    pub fn define_input(&mut self, name: impl Into<String>, bits: u32) {
+        self.named_inputs.insert(name.into(), bits);
+    }
+
+    /// Check if all bits of a named input were set in the inputs last received with
+    /// [`RGBMatrix::receive_new_inputs`] or [`RGBMatrix::drain_inputs`]. Returns `None` if no input with this
+    /// name was defined.
+    #[must_use]
+    pub fn input_pressed(&self, name: &str) -> Option<bool> {
+        let bits = *self.named_inputs.get(name)?;
+        Some(bits != 0 && self.last_inputs & bits == bits)
+    }
+
+    /// Get the best-effort setup steps of the update thread that failed, e.g. to tell the user that the display
+    /// runs without realtime priority and might flicker. These are printed on startup unless `quiet` is set.
+    #[must_use]
+    pub fn init_warnings(&self) -> &[InitWarning] {
+        &self.init_warnings
+    }
+
+    /// Get a snapshot of a displayed frame as RGB bytes, row by row, if one was taken since the last call.
+    /// Snapshots are only taken if [`RGBMatrixConfig::preview_interval`] is set.
+    #[must_use]
+    pub fn receive_preview(&self) -> Option<Vec<u8>> {
+        self.preview_receiver
+            .as_ref()
+            .and_then(SnapshotReceiver::try_recv)
     }
 
-    /// Get the average frame rate over the last 60 frames.
+    /// Get the configuration the matrix runs with, e.g. for logs and bug reports. The defaults that depend on the
+    /// board, like [`RGBMatrixConfig::slowdown`] and [`RGBMatrixConfig::cpu_core`], are resolved, and the
+    /// settings of the panel profile, the E pin and the multiplexing implied by the scan rate are applied.
+    #[must_use]
+    pub fn effective_config(&self) -> &RGBMatrixConfig {
+        &self.effective_config
+    }
+
+    /// Get the average frame rate over the last [`RGBMatrixConfig::framerate_window`] frames.
     #[must_use]
     pub fn get_framerate(&self) -> usize {
         self.frame_rate_monitor.get_fps().round() as usize
     }
+
+    /// Get the fraction of the last frame that the update thread spent writing to the matrix rather than
+    /// waiting. A value close to 1.0 means that the configured refresh rate can barely be reached.
+    #[must_use]
+    pub fn thread_cpu_load(&self) -> f32 {
+        f32::from_bits(self.thread_cpu_load.load(Ordering::Relaxed))
+    }
+
+    /// Get the number of canvases the update thread received through [`RGBMatrix::update_on_vsync`] and the
+    /// number of frames it wrote to the matrix, as `(submitted, displayed)`. Since every swap is followed by a
+    /// frame, `displayed` can only fall behind `submitted` if the swap handshake is broken.
+    #[must_use]
+    pub fn frame_counts(&self) -> (u64, u64) {
+        (
+            self.frame_counts.submitted.load(Ordering::Relaxed),
+            self.frame_counts.displayed.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Get the number of output enable pulses whose end the update thread could not detect and had to cut off
+    /// after a timeout. A growing count hints at a PWM problem that can show up as ghosting or uneven
+    /// brightness.
+    #[must_use]
+    pub fn pulse_timeouts(&self) -> u64 {
+        self.frame_counts.pulse_timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Get the PWM bits the update thread shows at most. This is lower than the configured PWM bits if they
+    /// were lowered to reach the refresh rate, see [`RGBMatrixConfig::min_pwm_bits`].
+    #[must_use]
+    pub fn adapted_pwm_bits(&self) -> usize {
+        self.frame_counts.adapted_pwm_bits.load(Ordering::Relaxed)
+    }
 }
 
 impl Drop for RGBMatrix {
     fn drop(&mut self) {
-        let Self {
-            thread_handle,
-            shutdown_sender,
-            ..
-        } = self;
-        if let Some(handle) = thread_handle.take() {
-            shutdown_sender.send(()).ok();
-            let _result = handle.join();
+        self.stop_thread();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{InitWarning, MatrixCreationError, RGBMatrix};
+    use crate::{
+        canvas::Canvas, config::K_BIT_PLANES, CoordinateMap, MultiplexMapperType,
+        NamedPixelMapperType, RGBMatrixConfig,
+    };
+
+    #[test]
+    fn test_dry_run() {
+        let config = RGBMatrixConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            RGBMatrix::new(config, 0),
+            Err(MatrixCreationError::DryRun)
+        ));
+    }
+
+    #[test]
+    fn test_init_warning_core_ids() {
+        // The core ids are 0-based like --cpu-core and isolcpus.
+        for warning in [
+            InitWarning::ThreadAffinity(2),
+            InitWarning::CoreNotIsolated(2),
+            InitWarning::PerformanceGovernor(2),
+        ] {
+            let message = warning.to_string();
+            assert!(message.contains('2') && !message.contains('3'), "{message}");
+        }
+    }
+
+    #[test]
+    fn test_multiplexed_mapping() {
+        for mapper_type in [
+            MultiplexMapperType::Stripe,
+            MultiplexMapperType::FlippedStripe,
+        ] {
+            for [rows, cols] in [[32, 64], [64, 64]] {
+                let mut config = RGBMatrixConfig {
+                    rows,
+                    cols,
+                    multiplexing: Some(mapper_type),
+                    ..Default::default()
+                };
+                let shared_mapper = RGBMatrix::create_shared_mapper(&mut config).unwrap();
+                assert_eq!(
+                    [shared_mapper.width(), shared_mapper.height()],
+                    [cols, rows]
+                );
+                assert_eq!([config.rows, config.cols], [rows / 2, cols * 2]);
+                assert!(shared_mapper.is_one_to_one());
+            }
         }
     }
+
+    #[test]
+    fn test_kaler_128_wide() {
+        // The Kaler mapper stretches a single 128 x 16 panel into a 512 x 4 matrix.
+        for rows in [16, 32] {
+            let mut config = RGBMatrixConfig {
+                rows,
+                cols: 128,
+                multiplexing: Some(MultiplexMapperType::Kaler2Scan),
+                ..Default::default()
+            };
+            let shared_mapper = RGBMatrix::create_shared_mapper(&mut config).unwrap();
+            assert_eq!([config.rows, config.cols], [rows / 4, 512]);
+            assert_eq!([shared_mapper.width(), shared_mapper.height()], [128, rows]);
+            assert!(shared_mapper.is_one_to_one());
+
+            // Every GPIO word of the lowest bit planes holds one pixel of the upper and one of the lower
+            // sub-panel.
+            let buffer_len = config.double_rows() * config.cols * K_BIT_PLANES;
+            let mut canvas = Canvas::new(&config, shared_mapper, [rows, 128]);
+            let mut gpio_words = HashMap::new();
+            for (x, y, gpio_word) in canvas.dump_mapping() {
+                let gpio_word = gpio_word.unwrap();
+                assert!(gpio_word < buffer_len, "({x}, {y}) is out of bounds");
+                assert!(gpio_word % (config.cols * K_BIT_PLANES) < config.cols);
+                *gpio_words.entry(gpio_word).or_insert(0) += 1;
+            }
+            assert_eq!(gpio_words.len(), config.double_rows() * config.cols);
+            assert!(gpio_words.values().all(|&count| count == 2));
+
+            // Drawing at the far corner and reading back the frame stays in bounds.
+            canvas.set_pixel(127, rows - 1, 255, 255, 255);
+            assert_eq!(canvas.get_color(127, rows - 1), [255, 255, 255]);
+            canvas.fill(10, 20, 30);
+            assert_eq!(canvas.to_rgb_bytes().len(), 128 * rows * 3);
+            let address_setter = config.row_setter.create(&config);
+            assert_ne!(address_setter.used_bits(), 0);
+        }
+    }
+
+    #[test]
+    fn test_u_mapper_widths() {
+        for cols in [32, 40, 64, 80, 96] {
+            let mut config = RGBMatrixConfig {
+                rows: 32,
+                cols,
+                chain_length: 4,
+                pixelmapper: vec![NamedPixelMapperType::UMapper],
+                ..Default::default()
+            };
+            let shared_mapper = RGBMatrix::create_shared_mapper(&mut config).unwrap();
+            assert_eq!(
+                [shared_mapper.width(), shared_mapper.height()],
+                [2 * cols, 64]
+            );
+            assert!(shared_mapper.is_one_to_one());
+        }
+    }
+
+    #[test]
+    fn test_pixel_mapper_error() {
+        let mut config = RGBMatrixConfig {
+            rows: 32,
+            cols: 32,
+            chain_length: 3,
+            pixelmapper: vec![
+                NamedPixelMapperType::Mirror(true),
+                NamedPixelMapperType::UMapper,
+            ],
+            ..Default::default()
+        };
+        let Err(error) = RGBMatrix::create_shared_mapper(&mut config) else {
+            panic!("The U-mapper needs an even chain length.");
+        };
+        assert!(matches!(
+            &error,
+            MatrixCreationError::PixelMapperError(2, mapper, _) if mapper == "UMapper"
+        ));
+        assert!(error.to_string().starts_with("Pixel mapper 2 (UMapper): "));
+    }
+
+    #[test]
+    fn test_custom_pixel_mapper() {
+        // Two 32 x 16 panels of a chain, the second one shown below the first one, with a hole in between.
+        let map = CoordinateMap::new((0..16).flat_map(|y| {
+            (0..32).flat_map(move |x| [([x, y], [x, y]), ([x, y + 20], [x + 32, y])])
+        }))
+        .unwrap();
+        assert_eq!([map.width(), map.height()], [32, 36]);
+        let mut config = RGBMatrixConfig {
+            rows: 16,
+            cols: 32,
+            chain_length: 2,
+            pixelmapper: vec![NamedPixelMapperType::Custom(map.clone())],
+            ..Default::default()
+        };
+        let shared_mapper = RGBMatrix::create_shared_mapper(&mut config).unwrap();
+        assert_eq!([shared_mapper.width(), shared_mapper.height()], [32, 36]);
+        assert!(!shared_mapper.is_one_to_one());
+        let mapping = Canvas::new(&config, shared_mapper, [16, 32]).dump_mapping();
+        let gpio_word = |x: usize, y: usize| mapping[y * 32 + x].2;
+        assert!(gpio_word(5, 3).is_some());
+        assert!(gpio_word(5, 18).is_none());
+        assert!(gpio_word(5, 23).is_some());
+        assert_ne!(gpio_word(5, 3), gpio_word(5, 23));
+
+        // The second panel is missing.
+        let mut config = RGBMatrixConfig {
+            chain_length: 1,
+            pixelmapper: vec![NamedPixelMapperType::Custom(map)],
+            ..config
+        };
+        assert!(matches!(
+            RGBMatrix::create_shared_mapper(&mut config),
+            Err(MatrixCreationError::InvalidCoordinateMap(32, 16))
+        ));
+
+        let map = CoordinateMap::from_table("# visible=matrix\n0,0=1,0\n1,0 = 0,0\n").unwrap();
+        assert_eq!([map.width(), map.height()], [2, 1]);
+        assert!(CoordinateMap::from_table("0,0=1,0\n1,0=1,0").is_err());
+        assert!(CoordinateMap::from_table("0,0=1,0\n0,0=2,0").is_err());
+        assert!(CoordinateMap::from_table("0,0").is_err());
+        assert!(CoordinateMap::from_table("").is_err());
+    }
 }
@@ -1,6 +1,39 @@
 use std::{error::Error, str::FromStr};
 
-use crate::{gpio::Gpio, RGBMatrixConfig};
+use crate::{gpio::Gpio, HardwareMapping, RGBMatrixConfig};
+
+/// One of the row address lines of the hardware mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressLine {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl AddressLine {
+    fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'A' => Some(Self::A),
+            'B' => Some(Self::B),
+            'C' => Some(Self::C),
+            'D' => Some(Self::D),
+            'E' => Some(Self::E),
+            _ => None,
+        }
+    }
+
+    fn bits(self, h: &HardwareMapping) -> u32 {
+        match self {
+            AddressLine::A => h.a,
+            AddressLine::B => h.b,
+            AddressLine::C => h.c,
+            AddressLine::D => h.d,
+            AddressLine::E => h.e,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RowAddressSetterType {
@@ -9,6 +42,13 @@ pub enum RowAddressSetterType {
     DirectABCDLine,
     ABCShiftRegister,
     SM5266,
+    /// A shift register row address setter with custom wiring, given as the clock line followed by the data line.
+    /// `ShiftRegister` is `ShiftRegister:AB` and `ABCShiftRegister` is `ShiftRegister:AC`.
+    /// Example: `--row-setter ShiftRegister:CB` for the clock on C and the data on B.
+    CustomShiftRegister {
+        clock: AddressLine,
+        data: AddressLine,
+    },
 }
 
 impl FromStr for RowAddressSetterType {
@@ -21,25 +61,57 @@ impl FromStr for RowAddressSetterType {
             "directabcdline" => Ok(Self::DirectABCDLine),
             "abcshiftregister" => Ok(Self::ABCShiftRegister),
             "sm5266" => Ok(Self::SM5266),
+            other if other.starts_with("shiftregister:") => {
+                let param = &s["shiftregister:".len()..];
+                let mut lines = param.chars().map(AddressLine::from_char);
+                match (lines.next(), lines.next(), lines.next()) {
+                    (Some(Some(clock)), Some(Some(data)), None) if clock != data => {
+                        Ok(Self::CustomShiftRegister { clock, data })
+                    }
+                    _ => Err(format!(
+                        "'{param}' is not valid. ShiftRegister parameter should be two different address lines \
+                         A to E, the clock followed by the data, e.g. 'CB'."
+                    )
+                    .into()),
+                }
+            }
             _ => Err(format!("'{s}' is not a valid row address setter type.").into()),
         }
     }
 }
 
 impl RowAddressSetterType {
+    /// The number of rows this row address setter can select, if limited.
+    pub(crate) const fn max_scanned_rows(self) -> Option<usize> {
+        match self {
+            RowAddressSetterType::Direct | RowAddressSetterType::SM5266 => Some(32),
+            RowAddressSetterType::DirectABCDLine => Some(4),
+            RowAddressSetterType::ShiftRegister
+            | RowAddressSetterType::ABCShiftRegister
+            | RowAddressSetterType::CustomShiftRegister { .. } => None,
+        }
+    }
+
     pub(crate) fn create(self, config: &RGBMatrixConfig) -> Box<dyn RowAddressSetter> {
         match self {
             RowAddressSetterType::Direct => Box::new(DirectRowAddressSetter::new(config)),
-            RowAddressSetterType::ShiftRegister => {
-                Box::new(ShiftRegisterRowAddressSetter::new(config))
-            }
+            RowAddressSetterType::ShiftRegister => Box::new(ShiftRegisterRowAddressSetter::new(
+                config,
+                AddressLine::A,
+                AddressLine::B,
+            )),
             RowAddressSetterType::DirectABCDLine => {
                 Box::new(DirectABCDLineRowAddressSetter::new(config))
             }
-            RowAddressSetterType::ABCShiftRegister => {
-                Box::new(ABCShiftRegisterRowAddressSetter::new(config))
-            }
+            RowAddressSetterType::ABCShiftRegister => Box::new(ShiftRegisterRowAddressSetter::new(
+                config,
+                AddressLine::A,
+                AddressLine::C,
+            )),
             RowAddressSetterType::SM5266 => Box::new(SM5266RowAddressSetter::new(config)),
+            RowAddressSetterType::CustomShiftRegister { clock, data } => {
+                Box::new(ShiftRegisterRowAddressSetter::new(config, clock, data))
+            }
         }
     }
 }
@@ -179,6 +251,8 @@ impl RowAddressSetter for SM5266RowAddressSetter {
     }
 }
 
+/// Shifts the row address into a shift register, one row per clock with the data low for the selected row.
+/// The plain shift register uses A for the clock and B for the data, the ABC variant C for the data.
 pub(crate) struct ShiftRegisterRowAddressSetter {
     row_mask: u32,
     last_row: Option<usize>,
@@ -188,11 +262,11 @@ pub(crate) struct ShiftRegisterRowAddressSetter {
 }
 
 impl ShiftRegisterRowAddressSetter {
-    pub(crate) fn new(config: &RGBMatrixConfig) -> Self {
+    pub(crate) fn new(config: &RGBMatrixConfig, clock: AddressLine, data: AddressLine) -> Self {
         let h = config.hardware_mapping;
-        let row_mask = h.a | h.b;
-        let clock = h.a;
-        let data = h.b;
+        let clock = clock.bits(&h);
+        let data = data.bits(&h);
+        let row_mask = clock | data;
         Self {
             row_mask,
             last_row: None,
@@ -227,55 +301,6 @@ impl RowAddressSetter for ShiftRegisterRowAddressSetter {
     }
 }
 
-/// A shift register row address setter that does not use B but C for the data. Clock is inverted.
-pub(crate) struct ABCShiftRegisterRowAddressSetter {
-    row_mask: u32,
-    last_row: Option<usize>,
-    clock: u32,
-    data: u32,
-    double_rows: usize,
-}
-
-impl ABCShiftRegisterRowAddressSetter {
-    pub(crate) fn new(config: &RGBMatrixConfig) -> Self {
-        let h = config.hardware_mapping;
-        let row_mask = h.a | h.c;
-        let clock = h.a;
-        let data = h.c;
-        Self {
-            row_mask,
-            last_row: None,
-            clock,
-            data,
-            double_rows: config.double_rows(),
-        }
-    }
-}
-
-impl RowAddressSetter for ABCShiftRegisterRowAddressSetter {
-    fn used_bits(&self) -> u32 {
-        self.row_mask
-    }
-
-    fn set_row_address(&mut self, gpio: &mut Gpio, row: usize) {
-        if self.last_row == Some(row) {
-            return;
-        }
-        (0..self.double_rows).for_each(|activate| {
-            gpio.clear_bits(self.clock);
-            if activate == self.double_rows - 1 - row {
-                gpio.clear_bits(self.data);
-            } else {
-                gpio.set_bits(self.data);
-            }
-            gpio.set_bits(self.clock);
-        });
-        gpio.clear_bits(self.clock);
-        gpio.set_bits(self.clock);
-        self.last_row = Some(row);
-    }
-}
-
 /// The [`DirectABCDLineRowAddressSetter`] sets the address by one of
 /// row pin ABCD for 32х16 matrix 1:4 multiplexing. The matrix has
 /// 4 addressable rows. Row is selected by a low level on the
@@ -323,3 +348,30 @@ impl RowAddressSetter for DirectABCDLineRowAddressSetter {
         self.last_row = Some(row);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressLine, RowAddressSetterType};
+
+    #[test]
+    fn test_parse_custom_shift_register() {
+        assert_eq!(
+            "ShiftRegister:CB".parse::<RowAddressSetterType>().unwrap(),
+            RowAddressSetterType::CustomShiftRegister {
+                clock: AddressLine::C,
+                data: AddressLine::B,
+            }
+        );
+        assert_eq!(
+            "shiftregister:ae".parse::<RowAddressSetterType>().unwrap(),
+            RowAddressSetterType::CustomShiftRegister {
+                clock: AddressLine::A,
+                data: AddressLine::E,
+            }
+        );
+        assert!("ShiftRegister:CC".parse::<RowAddressSetterType>().is_err());
+        assert!("ShiftRegister:C".parse::<RowAddressSetterType>().is_err());
+        assert!("ShiftRegister:CBA".parse::<RowAddressSetterType>().is_err());
+        assert!("ShiftRegister:XB".parse::<RowAddressSetterType>().is_err());
+    }
+}
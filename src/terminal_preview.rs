@@ -0,0 +1,103 @@
+use std::{
+    fmt::Write as _,
+    io::{stderr, Write},
+    mem::take,
+    thread::spawn,
+};
+
+use crate::{
+    frame_snapshot::{frame_snapshots, SnapshotSender},
+    Canvas,
+};
+
+/// The preview is scaled down by whole factors until it is at most this many characters wide.
+const MAX_COLUMNS: usize = 128;
+
+/// Prints a coarse preview of the displayed frames to the terminal, using ANSI true colors and half blocks for
+/// two pixels per character. The decoding and printing happen on a separate thread so that a slow terminal,
+/// e.g. over SSH, never stalls the display update. Frames that arrive while the previous one is still being
+/// printed are dropped.
+pub(crate) struct TerminalPreview {
+    snapshot_sender: SnapshotSender,
+}
+
+impl TerminalPreview {
+    pub(crate) fn new(canvas: &Canvas) -> Self {
+        let (width, height) = canvas.dimensions();
+        let (snapshot_sender, snapshot_receiver) = frame_snapshots(canvas);
+        spawn(move || {
+            // Clear the screen once, afterwards each frame is printed over the previous one.
+            let mut clear = "\x1b[2J".to_string();
+            while let Some(frame) = snapshot_receiver.recv() {
+                let output = take(&mut clear) + &render(&frame, width, height);
+                if stderr().write_all(output.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { snapshot_sender }
+    }
+
+    /// Hand the frame of the canvas to the printing thread. Drops the frame if the thread is still busy.
+    pub(crate) fn push(&self, canvas: &Canvas) {
+        self.snapshot_sender.push(canvas);
+    }
+}
+
+/// Render a frame of RGB bytes starting at the top left of the terminal. Each character shows the pixel of
+/// every `scale`th column, with the upper pixel as the foreground and the lower one as the background color of
+/// an upper half block.
+fn render(frame: &[u8], width: usize, height: usize) -> String {
+    let scale = width.div_ceil(MAX_COLUMNS).max(1);
+    let pixel = |x: usize, y: usize| {
+        let offset = 3 * (y * width + x);
+        frame.get(offset..offset + 3).unwrap_or(&[0, 0, 0])
+    };
+    let mut output = "\x1b[H".to_string();
+    for y in (0..height).step_by(2 * scale) {
+        for x in (0..width).step_by(scale) {
+            let upper = pixel(x, y);
+            let lower = if y + scale < height {
+                pixel(x, y + scale)
+            } else {
+                &[0, 0, 0]
+            };
+            write!(
+                output,
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                upper[0], upper[1], upper[2], lower[0], lower[1], lower[2]
+            )
+            .unwrap();
+        }
+        output.push_str("\x1b[0m\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, MAX_COLUMNS};
+
+    #[test]
+    fn test_render() {
+        // A red upper and a blue lower row.
+        let frame = [[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 0, 255]].concat();
+        let output = render(&frame, 2, 2);
+        assert_eq!(output.lines().count(), 1);
+        assert_eq!(
+            output
+                .matches("\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}")
+                .count(),
+            2
+        );
+
+        // Wide chains are scaled down to fit the terminal, odd heights get a black lower half.
+        let width = 3 * MAX_COLUMNS;
+        let output = render(&vec![255; width * 5 * 3], width, 5);
+        assert_eq!(output.lines().count(), 1);
+        assert_eq!(output.matches('\u{2580}').count(), MAX_COLUMNS);
+        let output = render(&[255; 4 * 3 * 3], 4, 3);
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.contains("\x1b[48;2;0;0;0m"));
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::Canvas;
+
+/// A [`Canvas`] whose dimensions are known at compile time. The dimensions are checked once when the canvas is
+/// wrapped, so that coordinates can be validated against the constants `W` and `H` instead of the canvas.
+///
+/// All other [`Canvas`] methods are available through [`Deref`].
+pub struct TypedCanvas<const W: usize, const H: usize> {
+    canvas: Box<Canvas>,
+}
+
+/// Compile-time check that a pixel lies within a `W` x `H` canvas.
+struct InBounds<const X: usize, const Y: usize, const W: usize, const H: usize>;
+
+impl<const X: usize, const Y: usize, const W: usize, const H: usize> InBounds<X, Y, W, H> {
+    const CHECK: () = assert!(X < W && Y < H, "Pixel is outside of the canvas.");
+}
+
+impl<const W: usize, const H: usize> TypedCanvas<W, H> {
+    /// Wrap a canvas. Returns the canvas unchanged if its dimensions are not `W` x `H`.
+    pub fn new(canvas: Box<Canvas>) -> Result<Self, Box<Canvas>> {
+        if canvas.width() == W && canvas.height() == H {
+            Ok(Self { canvas })
+        } else {
+            Err(canvas)
+        }
+    }
+
+    /// Unwrap the canvas, e.g. to pass it to [`crate::RGBMatrix::update_on_vsync`].
+    #[must_use]
+    pub fn into_inner(self) -> Box<Canvas> {
+        self.canvas
+    }
+
+    /// Set a pixel. Pixels outside of the `W` x `H` canvas are discarded.
+    pub fn set_pixel(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
+        if x < W && y < H {
+            self.canvas.set_pixel_unchecked(x, y, r, g, b);
+        }
+    }
+
+    /// Set a pixel whose coordinates are known at compile time. Coordinates outside of the canvas fail to
+    /// compile.
+    pub fn set_pixel_at<const X: usize, const Y: usize>(&mut self, r: u8, g: u8, b: u8) {
+        #[allow(clippy::let_unit_value)]
+        let () = InBounds::<X, Y, W, H>::CHECK;
+        self.canvas.set_pixel_unchecked(X, Y, r, g, b);
+    }
+}
+
+impl<const W: usize, const H: usize> Deref for TypedCanvas<W, H> {
+    type Target = Canvas;
+
+    fn deref(&self) -> &Self::Target {
+        &self.canvas
+    }
+}
+
+impl<const W: usize, const H: usize> DerefMut for TypedCanvas<W, H> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.canvas
+    }
+}
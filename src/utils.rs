@@ -37,27 +37,28 @@ pub(crate) fn linux_has_isol_cpu(cpu: usize) -> bool {
         .any(|line| line.unwrap().contains(&cpu.to_string()))
 }
 
+/// Pin the current thread to a core. Returns whether this succeeded.
 pub fn set_thread_affinity(core_id: usize) -> bool {
     let mut set: cpu_set_t = unsafe { std::mem::zeroed() };
     unsafe { CPU_SET(core_id, &mut set) }
     let cpusetsize = std::mem::size_of::<cpu_set_t>();
     let mask = &set;
     let res = unsafe { sched_setaffinity(0, cpusetsize, mask) };
-    res != 0
+    res == 0
 }
 
-const WINDOW_LENGTH: usize = 60;
-
 pub(crate) struct FrameRateMonitor {
-    times: [f32; WINDOW_LENGTH],
+    times: Vec<f32>,
     index: usize,
     last_time: Option<Instant>,
 }
 
 impl FrameRateMonitor {
-    pub(crate) fn new() -> Self {
+    /// Create a monitor that averages over the last `window_length` frames.
+    pub(crate) fn new(window_length: usize) -> Self {
+        let window_length = window_length.max(1);
         Self {
-            times: [1.0 / WINDOW_LENGTH as f32; WINDOW_LENGTH],
+            times: vec![1.0 / window_length as f32; window_length],
             index: 0,
             last_time: None,
         }
@@ -65,14 +66,15 @@ impl FrameRateMonitor {
 
     pub(crate) fn update(&mut self) {
         if let Some(last_time) = self.last_time.take() {
-            self.times[self.index % WINDOW_LENGTH] = last_time.elapsed().as_secs_f32();
+            let window_length = self.times.len();
+            self.times[self.index % window_length] = last_time.elapsed().as_secs_f32();
             self.index += 1;
         }
         self.last_time = Some(Instant::now());
     }
 
     pub(crate) fn get_fps(&self) -> f32 {
-        WINDOW_LENGTH as f32 / self.times.iter().sum::<f32>()
+        self.times.len() as f32 / self.times.iter().sum::<f32>()
     }
 }
 